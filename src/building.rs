@@ -1,46 +1,413 @@
-use rand::{seq::SliceRandom, thread_rng, Rng};
-use ratatui::widgets::{Bar, BarGroup};
+use crate::clock::{self, Instant};
+use crate::energy::{EnergyModel, Meter};
+use crate::events::{Event, EventLog};
+use crate::traffic::{CallLog, Pattern, Traffic};
+use clap::ValueEnum;
+use rand::{rngs::StdRng, seq::SliceRandom, thread_rng, Rng, SeedableRng};
+use ratatui::{
+    style::{Style, Stylize},
+    widgets::{Bar, BarGroup},
+};
 use std::{
-    sync::{Arc, RwLock},
+    ops::Range,
+    str::FromStr,
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc, RwLock,
+    },
     thread,
     time::Duration,
     vec::Vec,
 };
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub struct Passenger {
     pub from_floor: i32,
     pub to_floor: i32,
     riding: bool,
+    /// Every floor the passenger stops at on their journey, from origin to
+    /// destination, including any transfer floors in between.
+    itinerary: Vec<i32>,
+    /// Index into `itinerary` of the floor this leg starts from.
+    leg: usize,
+    /// Which deck of a double-deck car the passenger rides in, counting up
+    /// from the lower deck.
+    deck: i32,
+    priority: Priority,
+    /// How many people are travelling together, boarding and leaving the
+    /// car as one.
+    group: usize,
+    /// How long the passenger will wait for a lift before giving up and
+    /// taking the stairs.
+    patience: Option<Duration>,
+    called_at: Instant,
+    /// When the passenger first got into a lift.
+    boarded_at: Option<Instant>,
+    /// The number of the call the passenger made, counting from 1, or 0
+    /// before the call is answered.
+    id: u64,
+}
+
+/// How a passenger should be served. Everyone but normal passengers gets a
+/// car to themselves, which then makes no stops for anyone else. Emergency
+/// calls take the nearest car that has no other priority passenger in it;
+/// the rest wait for an empty one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    #[default]
+    Normal,
+    /// Wheelchair users and others who need room and time: the doors stay
+    /// open longer at their stops.
+    Accessibility,
+    Vip,
+    /// Dispatched to whichever lift can get there soonest, whatever the
+    /// dispatch strategy, and never held at the lobby.
+    Emergency,
+}
+
+impl Priority {
+    /// Whether passengers of this class only ever get into an empty car.
+    fn needs_empty_car(self) -> bool {
+        matches!(self, Priority::Accessibility | Priority::Vip)
+    }
+}
+
+impl FromStr for Priority {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "normal" => Ok(Priority::Normal),
+            "accessibility" => Ok(Priority::Accessibility),
+            "vip" => Ok(Priority::Vip),
+            "emergency" => Ok(Priority::Emergency),
+            _ => Err(format!(
+                "Invalid class '{}': expected normal, accessibility, vip or emergency",
+                s
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for Priority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Priority::Normal => write!(f, "normal"),
+            Priority::Accessibility => write!(f, "accessibility"),
+            Priority::Vip => write!(f, "VIP"),
+            Priority::Emergency => write!(f, "emergency"),
+        }
+    }
 }
 
 impl Passenger {
     pub fn new(from_floor: i32, to_floor: i32) -> Passenger {
+        Passenger::with_itinerary(vec![from_floor, to_floor])
+    }
+
+    fn with_itinerary(itinerary: Vec<i32>) -> Passenger {
+        Passenger {
+            from_floor: itinerary[0],
+            to_floor: itinerary[1],
+            riding: false,
+            itinerary,
+            leg: 0,
+            deck: 0,
+            priority: Priority::Normal,
+            group: 1,
+            patience: None,
+            called_at: Instant::now(),
+            boarded_at: None,
+            id: 0,
+        }
+    }
+
+    pub fn with_group(self, group: usize) -> Passenger {
         Passenger {
-            from_floor,
+            group: group.max(1),
+            ..self
+        }
+    }
+
+    /// How many people are travelling together.
+    pub fn group(&self) -> usize {
+        self.group
+    }
+
+    /// Whether the passenger has waited longer than they are willing to
+    /// for their first lift. Once they have changed lifts at a sky lobby
+    /// they stay the course.
+    fn out_of_patience(&self) -> bool {
+        self.leg == 0 && !self.riding && self.patience.is_some_and(|p| self.called_at.elapsed() > p)
+    }
+
+    /// Split off the first `size` people of a group, returning them and
+    /// the rest.
+    fn split(self, size: usize) -> (Passenger, Passenger) {
+        let rest = Passenger {
+            group: self.group - size,
+            ..self.clone()
+        };
+        (
+            Passenger {
+                group: size,
+                ..self
+            },
+            rest,
+        )
+    }
+
+    pub fn with_priority(self, priority: Priority) -> Passenger {
+        Passenger { priority, ..self }
+    }
+
+    /// The same journey, cut short at `floor` partway through this leg.
+    fn end_at(self, floor: i32) -> Passenger {
+        let mut itinerary = self.itinerary[..=self.leg].to_vec();
+        itinerary.push(floor);
+        Passenger {
+            to_floor: floor,
+            itinerary,
+            ..self
+        }
+    }
+
+    fn next_leg(&self) -> Option<Passenger> {
+        let to_floor = *self.itinerary.get(self.leg + 2)?;
+        Some(Passenger {
+            from_floor: self.to_floor,
             to_floor,
             riding: false,
+            itinerary: self.itinerary.clone(),
+            leg: self.leg + 1,
+            deck: 0,
+            priority: self.priority,
+            group: self.group,
+            patience: self.patience,
+            called_at: self.called_at,
+            boarded_at: self.boarded_at,
+            id: self.id,
+        })
+    }
+}
+
+impl std::fmt::Display for Passenger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} → {}", self.from_floor, self.to_floor)?;
+        if self.priority != Priority::Normal {
+            write!(f, " {}", self.priority)?;
+        }
+        if self.group > 1 {
+            write!(f, " x{}", self.group)?;
+        }
+        Ok(())
+    }
+}
+
+/// A completed journey, timed from the original call to arrival at the
+/// final destination.
+#[derive(Debug, Clone)]
+pub struct Journey {
+    pub legs: usize,
+    pub priority: Priority,
+    pub time: Duration,
+    /// How long the passenger waited for their first lift.
+    pub wait: Duration,
+    pub finished: Instant,
+}
+
+/// Live figures for the dashboard.
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    pub waiting: usize,
+    pub riding: usize,
+    /// The average and longest wait of everyone still waiting.
+    pub average_wait: Duration,
+    pub longest_wait: Duration,
+    /// Journeys completed in the last five minutes.
+    pub throughput: usize,
+    /// The waits of the most recent journeys, oldest first.
+    pub waits: Vec<Duration>,
+    /// Each lift's id and the share of its capacity taken by the people
+    /// riding in it or waiting for it.
+    pub utilisation: Vec<(u32, f64)>,
+}
+
+/// A contiguous range of floors served by a group of lifts. Neighbouring
+/// zones share a sky lobby, where passengers change lifts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Zone {
+    pub lowest: i32,
+    pub highest: i32,
+}
+
+impl Zone {
+    fn serves(&self, floor: i32) -> bool {
+        self.lowest <= floor && floor <= self.highest
+    }
+
+    fn transfer_floor(&self, other: &Zone, destination: i32) -> Option<i32> {
+        let lowest = self.lowest.max(other.lowest);
+        let highest = self.highest.min(other.highest);
+        if lowest > highest {
+            return None;
+        }
+        Some(destination.clamp(lowest, highest))
+    }
+}
+
+/// The layout of one lift bank, as given on the command line in the form
+/// `NAME:LIFTS[x2][:LOWEST:HIGHEST[:LOBBY]]`, where `x2` makes every car in
+/// the bank double-decked. A bank without floors serves the whole building,
+/// and its lobby defaults to the ground floor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BankSpec {
+    pub name: String,
+    pub lifts: u32,
+    pub decks: i32,
+    pub zone: Option<Zone>,
+    pub lobby: Option<i32>,
+}
+
+impl BankSpec {
+    /// One bank per zone between sky lobbies, sharing the lifts out as
+    /// evenly as possible.
+    pub fn zoned(
+        bottom_floor: i32,
+        top_floor: i32,
+        lift_count: u32,
+        decks: i32,
+        sky_lobbies: &[i32],
+    ) -> Vec<BankSpec> {
+        let zones = zones(bottom_floor, top_floor, sky_lobbies);
+        let count = zones.len() as u32;
+        zones
+            .iter()
+            .enumerate()
+            .map(|(i, zone)| BankSpec {
+                name: if count == 1 {
+                    "Lifts".to_string()
+                } else {
+                    format!("Zone {}", i + 1)
+                },
+                lifts: lift_count / count + u32::from((i as u32) < lift_count % count),
+                decks,
+                zone: Some(*zone),
+                lobby: None,
+            })
+            .collect()
+    }
+
+    /// Check that the bank's floors lie within a building from
+    /// `bottom_floor` to `top_floor`, and that its lobby is one of them.
+    pub fn check(&self, bottom_floor: i32, top_floor: i32) -> Result<(), String> {
+        let zone = self.zone.unwrap_or(Zone {
+            lowest: bottom_floor,
+            highest: top_floor,
+        });
+        if zone.lowest < bottom_floor || zone.highest > top_floor {
+            return Err(format!(
+                "Bank {} serves floors {} to {}, but the building only has floors {} to {}.",
+                self.name, zone.lowest, zone.highest, bottom_floor, top_floor
+            ));
         }
+        match self.lobby {
+            Some(lobby) if !zone.serves(lobby) => Err(format!(
+                "Bank {} has its lobby on floor {}, which it does not serve.",
+                self.name, lobby
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl FromStr for BankSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split(':').collect();
+        let number = |i: usize| -> Result<i32, String> {
+            fields[i]
+                .trim()
+                .parse::<i32>()
+                .map_err(|e| format!("Invalid number '{}': {}", fields[i], e))
+        };
+        let (zone, lobby) = match fields.len() {
+            2 => (None, None),
+            4 => (Some((number(2)?, number(3)?)), None),
+            5 => (Some((number(2)?, number(3)?)), Some(number(4)?)),
+            _ => return Err("Expected NAME:LIFTS[x2][:LOWEST:HIGHEST[:LOBBY]]".to_string()),
+        };
+        let zone = match zone {
+            Some((lowest, highest)) if lowest > highest => {
+                return Err(format!("Floor {} is above floor {}.", lowest, highest))
+            }
+            Some((lowest, highest)) => Some(Zone { lowest, highest }),
+            None => None,
+        };
+        let (lifts, decks) = match fields[1].trim().split_once('x') {
+            Some((lifts, "2")) => (lifts, 2),
+            Some((_, decks)) => return Err(format!("Cars cannot have {} decks.", decks)),
+            None => (fields[1].trim(), 1),
+        };
+        Ok(BankSpec {
+            name: fields[0].trim().to_string(),
+            lifts: lifts
+                .parse()
+                .map_err(|e| format!("Invalid lift count '{}': {}", lifts, e))?,
+            decks,
+            zone,
+            lobby,
+        })
     }
 }
 
+/// A group of lifts with its own lobby and dispatcher, serving one zone of
+/// the building.
 #[derive(Debug)]
-pub struct Building {
-    pub bottom_floor: i32,
-    pub top_floor: i32,
+pub struct Bank {
+    pub name: String,
+    pub lobby: i32,
+    zone: Zone,
     lifts: Vec<Arc<Lift>>,
 }
 
-impl Building {
-    pub fn new(bottom_floor: i32, top_floor: i32, lift_count: u32) -> Building {
-        let lifts = sequence(lift_count)
+impl Bank {
+    fn new(
+        spec: &BankSpec,
+        zone: Zone,
+        first_id: u32,
+        energy: EnergyModel,
+        mode: &Arc<RwLock<Mode>>,
+        log: &Arc<RwLock<EventLog>>,
+        handoff: &Sender<Passenger>,
+    ) -> Bank {
+        // Without a lobby of its own, a bank that does not reach the ground
+        // floor has its lobby at the nearest floor it serves.
+        let lobby = spec
+            .lobby
+            .unwrap_or_else(|| 0.clamp(zone.lowest, zone.highest));
+        let lifts = sequence(spec.lifts)
             .iter()
-            .map(|x| Arc::new(Lift::new(*x)))
+            .map(|x| {
+                Arc::new(
+                    Lift::new(
+                        first_id + x,
+                        lobby,
+                        spec.decks,
+                        energy,
+                        Arc::clone(mode),
+                        Arc::clone(log),
+                        handoff.clone(),
+                    )
+                    .with_zone(zone),
+                )
+            })
             .collect();
-        start_threads(&lifts);
-        Building {
-            bottom_floor,
-            top_floor,
+        Bank {
+            name: spec.name.clone(),
+            lobby,
+            zone,
             lifts,
         }
     }
@@ -49,6 +416,432 @@ impl Building {
         self.lifts.len() as u16
     }
 
+    /// The lowest and highest floors the bank serves.
+    pub fn floors(&self) -> (i32, i32) {
+        (self.zone.lowest, self.zone.highest)
+    }
+
+    pub fn cars(&self) -> Result<Vec<Car>, String> {
+        self.lifts.iter().map(|lift| lift.car()).collect()
+    }
+
+    /// How many people are waiting for this bank's lifts at each floor, and
+    /// how long the longest of them has waited, for floors where anyone is.
+    pub fn landings(&self) -> Result<Vec<(i32, usize, Duration)>, String> {
+        let mut landings = Vec::new();
+        for lift in &self.lifts {
+            let passengers = lift
+                .passengers
+                .read()
+                .map_err(|e| format!("Failed to read-lock passengers: {}", e))?;
+            for passenger in passengers.iter().filter(|p| !p.riding) {
+                let landing = (
+                    passenger.from_floor,
+                    passenger.group,
+                    passenger.called_at.elapsed(),
+                );
+                add_landing(&mut landings, landing);
+            }
+        }
+        Ok(landings)
+    }
+
+    fn serves_leg(&self, passenger: &Passenger) -> bool {
+        self.zone.serves(passenger.from_floor) && self.zone.serves(passenger.to_floor)
+    }
+
+    /// This bank's dispatcher: the index of the lift with room for at
+    /// least `room` more people that can serve the passenger at the lowest
+    /// cost, and what that cost is. Accessibility and VIP passengers are
+    /// only given an empty car.
+    fn best_lift(
+        &self,
+        passenger: &Passenger,
+        dispatch: Dispatch,
+        room: usize,
+    ) -> Result<(usize, f64), String> {
+        match passenger.priority {
+            Priority::Normal => self.cheapest_lift(passenger, dispatch, false, room),
+            Priority::Emergency => self.cheapest_lift(passenger, Dispatch::Wait, false, room),
+            Priority::Accessibility | Priority::Vip => {
+                self.cheapest_lift(passenger, dispatch, true, room)
+            }
+        }
+    }
+
+    fn cheapest_lift(
+        &self,
+        passenger: &Passenger,
+        dispatch: Dispatch,
+        empty_only: bool,
+        room: usize,
+    ) -> Result<(usize, f64), String> {
+        let mut best = None;
+        let mut cheapest = f64::MAX;
+        let lifts = &self.lifts;
+        let mut indices: Vec<usize> = (0..lifts.len()).collect();
+        indices.shuffle(&mut thread_rng());
+        for index in indices {
+            let lift = &lifts[index];
+            if lift.fault()?.is_some() || lift.service()? != Service::Normal {
+                continue;
+            }
+            if lift.exclusive()? || (empty_only && lift.has_passengers()?) || lift.room()? < room {
+                continue;
+            }
+            let cost = match dispatch {
+                Dispatch::Wait => lift.distance_from(passenger).map(f64::from),
+                Dispatch::Energy => lift.energy_cost(passenger),
+                Dispatch::Etd => lift.time_to_destination(passenger),
+            };
+            if let Ok(cost) = cost {
+                if cost < cheapest {
+                    cheapest = cost;
+                    best = Some((index, cost));
+                }
+            }
+        }
+        best.ok_or(format!("{} has no lifts.", self.name))
+    }
+
+    /// Send every lift that has been idle for long enough to wherever the
+    /// parking policy wants it. `demand` lists floors from the busiest down.
+    fn park(&self, parking: Parking, demand: &[i32]) -> Result<(), String> {
+        let mut taken = Vec::new();
+        let mut waiting = Vec::new();
+        for lift in &self.lifts {
+            let (floor, _, _) = lift.get_info()?;
+            if lift.fault()?.is_some() || lift.service()? != Service::Normal {
+                continue;
+            }
+            match lift.idle()? {
+                Idle::Since(since) if since.elapsed() >= PARK_DELAY => waiting.push((lift, floor)),
+                Idle::Since(_) | Idle::Parked => taken.push(floor),
+                Idle::Parking(floor) => taken.push(floor),
+                Idle::Busy | Idle::Sweeping => {}
+            }
+        }
+        let count = self.lifts.len() as i32;
+        let slots = |lowest: i32| -> Vec<i32> {
+            let span = self.zone.highest - lowest;
+            (0..count)
+                .map(|i| lowest + (2 * i + 1) * span / (2 * count))
+                .collect()
+        };
+        let middle = self.zone.lowest + (self.zone.highest - self.zone.lowest) / 2;
+        for (lift, floor) in waiting {
+            let choices = match parking {
+                Parking::Stay => vec![],
+                Parking::Lobby => vec![self.lobby],
+                Parking::Spread | Parking::Upper => {
+                    let lowest = if parking == Parking::Upper {
+                        middle
+                    } else {
+                        self.zone.lowest
+                    };
+                    let mut slots = slots(lowest);
+                    slots.sort_by_key(|slot| difference(*slot, floor));
+                    slots
+                }
+                Parking::Demand => demand
+                    .iter()
+                    .copied()
+                    .filter(|f| self.zone.serves(*f))
+                    .chain([self.lobby])
+                    .collect(),
+            };
+            let choice = choices
+                .iter()
+                .copied()
+                .find(|f| parking == Parking::Lobby || !taken.contains(f))
+                .unwrap_or(floor);
+            taken.push(choice);
+            lift.park(choice)?;
+        }
+        Ok(())
+    }
+}
+
+/// Where lifts go once they have nothing left to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Parking {
+    /// Stop wherever the last passenger got out.
+    Stay,
+    /// Return to the bank's lobby.
+    Lobby,
+    /// Spread out evenly across the floors the bank serves.
+    Spread,
+    /// Spread out across the upper half of the bank's floors.
+    Upper,
+    /// Wait at the floors where most recent calls have come from.
+    Demand,
+}
+
+/// How long a lift waits without work before it is parked.
+const PARK_DELAY: Duration = Duration::from_millis(3000);
+
+/// How the group controller runs the lifts, to suit the time of day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Mode {
+    /// Park idle lifts according to the parking policy.
+    Balanced,
+    /// Morning rush: lifts return to the lobby and wait there until they
+    /// are full or the hold time runs out.
+    UpPeak,
+    /// Evening rush: idle lifts go up to the upper floors and sweep down
+    /// through them to the lobby, where they are sent up again, taking
+    /// anyone going down on the way.
+    DownPeak,
+}
+
+impl std::fmt::Display for Mode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Mode::Balanced => write!(f, "balanced"),
+            Mode::UpPeak => write!(f, "up-peak"),
+            Mode::DownPeak => write!(f, "down-peak"),
+        }
+    }
+}
+
+/// How long a lift in up-peak mode holds its doors open at the lobby for
+/// more passengers before setting off.
+const LOBBY_HOLD: Duration = Duration::from_millis(5000);
+
+/// How far back the dashboard counts completed journeys.
+const THROUGHPUT_WINDOW: Duration = Duration::from_secs(300);
+
+/// How passengers behave when a lift is slow to come or hardly worth
+/// waiting for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Behaviour {
+    /// How long passengers wait for a lift before giving up.
+    pub patience: Option<Duration>,
+    /// Trips of this many floors or fewer may be walked instead.
+    pub stairs_floors: i32,
+    /// The share of short trips that are walked.
+    pub stairs_share: f64,
+}
+
+impl Default for Behaviour {
+    fn default() -> Self {
+        Behaviour {
+            patience: None,
+            stairs_floors: 0,
+            stairs_share: 0.5,
+        }
+    }
+}
+
+/// What a dispatcher tries to minimise when choosing a lift.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Dispatch {
+    /// Send the lift that will reach the passenger soonest.
+    Wait,
+    /// Send the lift that will use the least energy to serve the passenger.
+    Energy,
+    /// Send the lift that will get the passenger to their floor soonest,
+    /// allowing for the delay their stops add for everyone else in it.
+    Etd,
+}
+
+#[derive(Debug)]
+pub struct Building {
+    pub bottom_floor: i32,
+    pub top_floor: i32,
+    banks: Vec<Bank>,
+    dispatch: RwLock<Dispatch>,
+    parking: RwLock<Parking>,
+    mode: Arc<RwLock<Mode>>,
+    auto_mode: RwLock<bool>,
+    calls: RwLock<CallLog>,
+    journeys: RwLock<Vec<Journey>>,
+    recalled: RwLock<bool>,
+    behaviour: RwLock<Behaviour>,
+    /// How many people gave up waiting, and how many walked rather than
+    /// call a lift.
+    abandoned: RwLock<(usize, usize)>,
+    /// Random numbers for generated passengers and for who walks, which
+    /// can be seeded to repeat a run.
+    rng: RwLock<StdRng>,
+    log: Arc<RwLock<EventLog>>,
+    /// How many calls have been answered, for numbering passengers.
+    answered: RwLock<u64>,
+    /// Passengers waiting for an empty car to be free for them.
+    held: RwLock<Vec<Passenger>>,
+}
+
+impl Building {
+    pub fn new(
+        bottom_floor: i32,
+        top_floor: i32,
+        specs: &[BankSpec],
+        energy: EnergyModel,
+    ) -> Arc<Building> {
+        let (building, arrivals) = Building::unstarted(bottom_floor, top_floor, specs, energy);
+        let building = Arc::new(building);
+        for bank in &building.banks {
+            start_threads(&bank.lifts);
+        }
+        start_controller(Arc::clone(&building), arrivals);
+        building
+    }
+
+    /// A building whose lifts and controller are not yet running, and the
+    /// channel its lifts hand passengers off into.
+    pub fn unstarted(
+        bottom_floor: i32,
+        top_floor: i32,
+        specs: &[BankSpec],
+        energy: EnergyModel,
+    ) -> (Building, Receiver<Passenger>) {
+        let (handoff, arrivals) = mpsc::channel();
+        let mode = Arc::new(RwLock::new(Mode::Balanced));
+        let log = Arc::new(RwLock::new(EventLog::default()));
+        let mut banks = Vec::new();
+        let mut first_id = 0;
+        for spec in specs {
+            let zone = spec.zone.unwrap_or(Zone {
+                lowest: bottom_floor,
+                highest: top_floor,
+            });
+            banks.push(Bank::new(
+                spec, zone, first_id, energy, &mode, &log, &handoff,
+            ));
+            first_id += spec.lifts;
+        }
+        let building = Building {
+            bottom_floor,
+            top_floor,
+            banks,
+            dispatch: RwLock::new(Dispatch::Wait),
+            parking: RwLock::new(Parking::Stay),
+            mode,
+            auto_mode: RwLock::new(false),
+            calls: RwLock::new(CallLog::default()),
+            journeys: RwLock::new(Vec::new()),
+            recalled: RwLock::new(false),
+            behaviour: RwLock::new(Behaviour::default()),
+            abandoned: RwLock::new((0, 0)),
+            rng: RwLock::new(StdRng::from_entropy()),
+            log,
+            answered: RwLock::new(0),
+            held: RwLock::new(Vec::new()),
+        };
+        (building, arrivals)
+    }
+
+    pub fn set_dispatch(&self, new_dispatch: Dispatch) -> Result<(), String> {
+        let mut dispatch = self
+            .dispatch
+            .write()
+            .map_err(|e| format!("Failed to write-lock dispatch: {}", e))?;
+        *dispatch = new_dispatch;
+        Ok(())
+    }
+
+    pub fn set_behaviour(&self, new_behaviour: Behaviour) -> Result<(), String> {
+        let mut behaviour = self
+            .behaviour
+            .write()
+            .map_err(|e| format!("Failed to write-lock behaviour: {}", e))?;
+        *behaviour = new_behaviour;
+        Ok(())
+    }
+
+    pub fn set_parking(&self, new_parking: Parking) -> Result<(), String> {
+        let mut parking = self
+            .parking
+            .write()
+            .map_err(|e| format!("Failed to write-lock parking: {}", e))?;
+        *parking = new_parking;
+        drop(parking);
+        self.unpark()
+    }
+
+    /// The current operating mode, and whether it is chosen automatically.
+    pub fn mode(&self) -> Result<(Mode, bool), String> {
+        let mode = *self
+            .mode
+            .read()
+            .map_err(|e| format!("Failed to read-lock mode: {}", e))?;
+        let auto_mode = *self
+            .auto_mode
+            .read()
+            .map_err(|e| format!("Failed to read-lock auto_mode: {}", e))?;
+        Ok((mode, auto_mode))
+    }
+
+    /// Fix the operating mode, or with `None` let the controller choose it
+    /// from the recent traffic.
+    pub fn set_mode(&self, new_mode: Option<Mode>) -> Result<(), String> {
+        let mut auto_mode = self
+            .auto_mode
+            .write()
+            .map_err(|e| format!("Failed to write-lock auto_mode: {}", e))?;
+        *auto_mode = new_mode.is_none();
+        drop(auto_mode);
+        match new_mode {
+            Some(mode) => self.switch_mode(mode),
+            None => self.update_mode(),
+        }
+    }
+
+    fn switch_mode(&self, new_mode: Mode) -> Result<(), String> {
+        let mut mode = self
+            .mode
+            .write()
+            .map_err(|e| format!("Failed to write-lock mode: {}", e))?;
+        if *mode == new_mode {
+            return Ok(());
+        }
+        *mode = new_mode;
+        drop(mode);
+        self.unpark()
+    }
+
+    /// In automatic mode, switch to whichever mode suits the pattern of
+    /// recent traffic.
+    fn update_mode(&self) -> Result<(), String> {
+        if !self.mode()?.1 {
+            return Ok(());
+        }
+        let mode = match self.traffic()?.pattern {
+            Pattern::Incoming => Mode::UpPeak,
+            Pattern::Outgoing => Mode::DownPeak,
+            Pattern::Quiet | Pattern::Interfloor | Pattern::Mixed => Mode::Balanced,
+        };
+        self.switch_mode(mode)
+    }
+
+    /// The mix of recent calls into, out of and around the building, and
+    /// how often they are arriving.
+    pub fn traffic(&self) -> Result<Traffic, String> {
+        let calls = self
+            .calls
+            .read()
+            .map_err(|e| format!("Failed to read-lock calls: {}", e))?;
+        Ok(calls.traffic(|floor| self.banks.iter().any(|b| b.lobby == floor)))
+    }
+
+    /// Let parked lifts be parked again, after the policy or mode changes.
+    fn unpark(&self) -> Result<(), String> {
+        for lift in self.banks.iter().flat_map(|b| &b.lifts) {
+            if let Idle::Parking(_) | Idle::Parked | Idle::Sweeping = lift.idle()? {
+                lift.set_idle(Idle::Since(Instant::now()))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn banks(&self) -> &[Bank] {
+        &self.banks
+    }
+
+    pub fn lift_count(&self) -> u32 {
+        self.banks.iter().map(|b| b.lifts.len() as u32).sum()
+    }
+
     fn abs_floor(&self, floor: i32) -> u64 {
         let value = floor - self.bottom_floor;
         if value < 0 {
@@ -57,21 +850,42 @@ impl Building {
         value as u64
     }
 
+    /// How many people are waiting at each floor across every bank, and
+    /// how long the longest of them has waited, for floors where anyone is.
+    pub fn landings(&self) -> Result<Vec<(i32, usize, Duration)>, String> {
+        let mut landings = Vec::new();
+        for bank in &self.banks {
+            for landing in bank.landings()? {
+                add_landing(&mut landings, landing);
+            }
+        }
+        Ok(landings)
+    }
+
     pub fn max_value(&self) -> u64 {
         difference(self.bottom_floor, self.top_floor) as u64
     }
 
-    pub fn data(&self) -> Result<BarGroup, String> {
+    /// The bank's lifts in `lifts` as bars, with the selected lift
+    /// highlighted.
+    pub fn data(
+        &self,
+        bank: &Bank,
+        selected: u32,
+        lifts: Range<usize>,
+    ) -> Result<BarGroup<'_>, String> {
         let mut bars = Vec::new();
-        for lift in &self.lifts {
+        for lift in &bank.lifts[lifts] {
             let (floor, _, _) = lift.get_info()?;
             let label = lift.label()?;
             // Bar::default().value(10).label("e".into())
-            bars.push(
-                Bar::default()
-                    .value(self.abs_floor(floor))
-                    .label(label.into()),
-            );
+            let mut bar = Bar::default()
+                .value(self.abs_floor(floor))
+                .label(label.into());
+            if lift.id()? == selected {
+                bar = bar.style(Style::new().yellow());
+            }
+            bars.push(bar);
         }
         Ok(BarGroup::default().bars(bars.as_slice()))
     }
@@ -86,83 +900,1239 @@ impl Building {
     //     Ok(output)
     // }
 
-    pub fn respond(&self, passenger: Passenger) -> Result<usize, String> {
-        if let Ok(index) = self.best_lift(&passenger) {
-            self.lifts[index].add_passenger(passenger)?;
-            return Ok(index);
+    /// Route the passenger's next leg to the bank that can pick them up
+    /// soonest, and hand it to that bank's dispatcher. Returns the id of the
+    /// lift that was assigned, or `None` if the passenger took the stairs or
+    /// is waiting for an empty car.
+    pub fn respond(&self, passenger: Passenger) -> Result<Option<u32>, String> {
+        if self.recalled()? {
+            return Err("The lifts have been recalled for a fire.".to_string());
+        }
+        if passenger.from_floor == passenger.to_floor {
+            return Err(format!(
+                "Passenger is already on floor {}.",
+                passenger.to_floor
+            ));
+        }
+        let passenger = if passenger.id == 0 {
+            let mut answered = self
+                .answered
+                .write()
+                .map_err(|e| format!("Failed to write-lock answered: {}", e))?;
+            *answered += 1;
+            Passenger {
+                id: *answered,
+                ..passenger
+            }
+        } else {
+            passenger
+        };
+        let passenger = if passenger.leg == 0 && passenger.itinerary.len() == 2 {
+            let behaviour = *self
+                .behaviour
+                .read()
+                .map_err(|e| format!("Failed to read-lock behaviour: {}", e))?;
+            if difference(passenger.from_floor, passenger.to_floor) <= behaviour.stairs_floors
+                && self
+                    .rng
+                    .write()
+                    .map_err(|e| format!("Failed to write-lock rng: {}", e))?
+                    .gen_bool(behaviour.stairs_share.clamp(0.0, 1.0))
+            {
+                self.count_abandoned(0, passenger.group)?;
+                self.log(
+                    None,
+                    format!(
+                        "Passenger #{} ({}) took the stairs",
+                        passenger.id, passenger
+                    ),
+                )?;
+                return Ok(None);
+            }
+            self.plan(Passenger {
+                patience: passenger.patience.or(behaviour.patience),
+                ..passenger
+            })?
+        } else {
+            passenger
+        };
+        // A transfer at a sky lobby is the same journey, not a new call.
+        if passenger.leg == 0 {
+            self.log_call(&passenger)?;
+        }
+        self.assign(passenger)
+    }
+
+    fn count_abandoned(&self, gave_up: usize, walked: usize) -> Result<(), String> {
+        let mut abandoned = self
+            .abandoned
+            .write()
+            .map_err(|e| format!("Failed to write-lock abandoned: {}", e))?;
+        abandoned.0 += gave_up;
+        abandoned.1 += walked;
+        Ok(())
+    }
+
+    /// Count a passenger who can no longer be taken where they are going as
+    /// having given up, and log why.
+    fn give_up(&self, passenger: &Passenger, reason: &str) -> Result<(), String> {
+        self.count_abandoned(passenger.group, 0)?;
+        self.log(
+            None,
+            format!(
+                "Passenger #{} ({}) gave up at {}: {}",
+                passenger.id, passenger, passenger.from_floor, reason
+            ),
+        )
+    }
+
+    /// Let go of everyone who has run out of patience waiting for a lift.
+    fn abandon(&self) -> Result<(), String> {
+        for lift in self.banks.iter().flat_map(|b| &b.lifts) {
+            let gave_up = lift.take_waiting(Passenger::out_of_patience)?;
+            for passenger in &gave_up {
+                lift.log(format!(
+                    "Passenger #{} gave up waiting for lift {} at {}",
+                    passenger.id,
+                    lift.id()?,
+                    passenger.from_floor
+                ))?;
+            }
+            self.count_abandoned(gave_up.iter().map(|p| p.group).sum(), 0)?;
+        }
+        let gave_up = self.take_held(Passenger::out_of_patience)?;
+        for passenger in &gave_up {
+            self.log(
+                None,
+                format!(
+                    "Passenger #{} gave up waiting for an empty car at {}",
+                    passenger.id, passenger.from_floor
+                ),
+            )?;
+        }
+        self.count_abandoned(gave_up.iter().map(|p| p.group).sum(), 0)
+    }
+
+    /// Keep a passenger who needs a car to themselves until one is free.
+    fn hold(&self, passenger: Passenger) -> Result<(), String> {
+        let message = format!(
+            "Passenger #{} ({}) waiting for an empty car",
+            passenger.id, passenger
+        );
+        self.held
+            .write()
+            .map_err(|e| format!("Failed to write-lock held: {}", e))?
+            .push(passenger);
+        self.log(None, message)
+    }
+
+    /// Take the held passengers that match the filter.
+    fn take_held(
+        &self,
+        mut filter: impl FnMut(&Passenger) -> bool,
+    ) -> Result<Vec<Passenger>, String> {
+        let mut held = self
+            .held
+            .write()
+            .map_err(|e| format!("Failed to write-lock held: {}", e))?;
+        let (taken, kept): (Vec<Passenger>, Vec<Passenger>) =
+            held.drain(..).partition(|p| filter(p));
+        *held = kept;
+        Ok(taken)
+    }
+
+    /// Give each held passenger a car as soon as one is free for them.
+    fn release(&self) -> Result<(), String> {
+        let free = |passenger: &Passenger| matches!(self.best_lift(passenger, 0), Ok(Some(_)));
+        for passenger in self.take_held(free)? {
+            self.assign(passenger)?;
         }
-        Err(format!("Could not respond to passenger: {:?}.", passenger))
+        Ok(())
+    }
+
+    /// Add an event to the log, about one lift or the building as a whole.
+    pub fn log(&self, lift: Option<u32>, message: String) -> Result<(), String> {
+        self.log
+            .write()
+            .map_err(|e| format!("Failed to write-lock log: {}", e))?
+            .push(lift, message);
+        Ok(())
+    }
+
+    /// Up to `count` recent events about one lift or all of them, skipping
+    /// the `skip` most recent, oldest first.
+    pub fn events(
+        &self,
+        lift: Option<u32>,
+        count: usize,
+        skip: usize,
+    ) -> Result<Vec<Event>, String> {
+        let log = self
+            .log
+            .read()
+            .map_err(|e| format!("Failed to read-lock log: {}", e))?;
+        Ok(log.recent(lift, count, skip))
+    }
+
+    /// How many people gave up waiting, how many took the stairs instead of
+    /// calling a lift, and the share of those who called a lift and have
+    /// either arrived or given up that gave up.
+    pub fn abandonment(&self) -> Result<(usize, usize, f64), String> {
+        let (gave_up, walked) = *self
+            .abandoned
+            .read()
+            .map_err(|e| format!("Failed to read-lock abandoned: {}", e))?;
+        let arrived = self.journey_summary()?.0;
+        let rate = if gave_up > 0 {
+            gave_up as f64 / (gave_up + arrived) as f64
+        } else {
+            0.0
+        };
+        Ok((gave_up, walked, rate))
+    }
+
+    /// Hand a passenger who has already been routed to the best lift in
+    /// service. A group that does not fit in one car is split between as
+    /// many as it takes, and the id of the lift taking the first of them is
+    /// returned. Someone who needs an empty car when there is none is held
+    /// until there is.
+    fn assign(&self, passenger: Passenger) -> Result<Option<u32>, String> {
+        if passenger.group > 1 {
+            for room in [passenger.group, 1] {
+                if let Some(lift) = self.best_lift(&passenger, room)? {
+                    let space = lift.room()?.max(1);
+                    if passenger.group <= space {
+                        self.give(lift, passenger)?;
+                        return lift.id().map(Some);
+                    }
+                    let (party, rest) = passenger.split(space);
+                    self.give(lift, party)?;
+                    if let Err(e) = self.assign(rest.clone()) {
+                        self.give_up(&rest, &e)?;
+                    }
+                    return lift.id().map(Some);
+                }
+            }
+        }
+        // Anyone who cannot fit in any car waits for the best one anyway.
+        if let Some(lift) = self.best_lift(&passenger, 0)? {
+            self.give(lift, passenger)?;
+            return lift.id().map(Some);
+        }
+        if passenger.priority.needs_empty_car()
+            && self.banks.iter().any(|b| b.serves_leg(&passenger))
+        {
+            self.hold(passenger)?;
+            return Ok(None);
+        }
+        Err(format!(
+            "No lift in service can take passenger #{} ({}).",
+            passenger.id, passenger
+        ))
+    }
+
+    fn give(&self, lift: &Arc<Lift>, passenger: Passenger) -> Result<(), String> {
+        let eta = Duration::from_millis(lift.distance_from(&passenger)? as u64 * MS_PER_FLOOR);
+        let message = format!(
+            "Passenger #{} ({}) assigned to lift {} (ETA {:.1}s)",
+            passenger.id,
+            passenger,
+            lift.id()?,
+            eta.as_secs_f64()
+        );
+        lift.add_passenger(passenger)?;
+        lift.log(message)
+    }
+
+    /// The cheapest lift in any bank that serves the passenger's leg and has
+    /// room for at least `room` more people.
+    fn best_lift(&self, passenger: &Passenger, room: usize) -> Result<Option<&Arc<Lift>>, String> {
+        let dispatch = *self
+            .dispatch
+            .read()
+            .map_err(|e| format!("Failed to read-lock dispatch: {}", e))?;
+        let mut best = None;
+        let mut cheapest = f64::MAX;
+        for bank in self.banks.iter().filter(|b| b.serves_leg(passenger)) {
+            if let Ok((index, cost)) = bank.best_lift(passenger, dispatch, room) {
+                if cost < cheapest {
+                    cheapest = cost;
+                    best = Some(&bank.lifts[index]);
+                }
+            }
+        }
+        Ok(best)
+    }
+
+    /// The lift with this id, and the bank it belongs to.
+    fn find(&self, id: u32) -> Result<(&Bank, &Arc<Lift>), String> {
+        for bank in &self.banks {
+            for lift in &bank.lifts {
+                if lift.id()? == id {
+                    return Ok((bank, lift));
+                }
+            }
+        }
+        Err(format!("There is no lift {}.", id))
+    }
+
+    fn lift(&self, id: u32) -> Result<&Arc<Lift>, String> {
+        self.find(id).map(|(_, lift)| lift)
+    }
+
+    /// Put a fault on a lift. It stops taking calls, and anyone still
+    /// waiting for it is assigned another lift, or gives up if none can
+    /// take them. Passengers already aboard are let off at their floors if
+    /// the lift is only out of service, but are stuck until it is repaired
+    /// if it has broken down.
+    pub fn fail(&self, id: u32, fault: Fault) -> Result<(), String> {
+        let lift = self.lift(id)?;
+        lift.set_fault(Some(fault))?;
+        lift.log(format!("Lift {} {}", id, fault))?;
+        for passenger in lift.take_waiting(|_| true)? {
+            if let Err(e) = self.assign(passenger.clone()) {
+                self.give_up(&passenger, &e)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn repair(&self, id: u32) -> Result<(), String> {
+        let lift = self.lift(id)?;
+        lift.set_fault(None)?;
+        lift.log(format!("Lift {} repaired", id))
+    }
+
+    pub fn recalled(&self) -> Result<bool, String> {
+        let recalled = *self
+            .recalled
+            .read()
+            .map_err(|e| format!("Failed to read-lock recalled: {}", e))?;
+        Ok(recalled)
+    }
+
+    /// Fire recall: send every lift nonstop to the recall floor, or to its
+    /// bank's lobby if the bank does not serve it, and hold it there with
+    /// its doors open. Anyone riding gets out there, anyone waiting gives
+    /// up, and no more calls are taken until the recall ends.
+    pub fn recall(&self, floor: Option<i32>) -> Result<(), String> {
+        let mut recalled = self
+            .recalled
+            .write()
+            .map_err(|e| format!("Failed to write-lock recalled: {}", e))?;
+        *recalled = true;
+        drop(recalled);
+        match floor {
+            Some(floor) => self.log(None, format!("Fire recall to floor {}", floor))?,
+            None => self.log(None, "Fire recall to the lobbies".to_string())?,
+        }
+        for bank in &self.banks {
+            let floor = floor.filter(|f| bank.zone.serves(*f)).unwrap_or(bank.lobby);
+            for lift in &bank.lifts {
+                for passenger in lift.recall(floor)? {
+                    self.give_up(&passenger, "The lifts have been recalled for a fire.")?;
+                }
+            }
+        }
+        for passenger in self.take_held(|_| true)? {
+            self.give_up(&passenger, "The lifts have been recalled for a fire.")?;
+        }
+        Ok(())
+    }
+
+    pub fn end_recall(&self) -> Result<(), String> {
+        let mut recalled = self
+            .recalled
+            .write()
+            .map_err(|e| format!("Failed to write-lock recalled: {}", e))?;
+        *recalled = false;
+        drop(recalled);
+        self.log(None, "Fire recall ended".to_string())?;
+        for lift in self.banks.iter().flat_map(|b| &b.lifts) {
+            lift.set_service(Service::Normal)?;
+            lift.set_doors_open(false)?;
+            lift.set_idle(Idle::Since(Instant::now()))?;
+        }
+        Ok(())
+    }
+
+    pub fn independent(&self, id: u32) -> Result<bool, String> {
+        Ok(self.lift(id)?.service()? == Service::Independent)
+    }
+
+    /// Take a lift out of group control, or give it back. On independent
+    /// service a lift answers car calls only, and waits at each floor with
+    /// its doors open until it is given the next one. Anyone still waiting
+    /// for it is assigned another lift, or gives up if none can take them.
+    pub fn set_independent(&self, id: u32, independent: bool) -> Result<(), String> {
+        if self.recalled()? {
+            return Err("The lifts have been recalled for a fire.".to_string());
+        }
+        let lift = self.lift(id)?;
+        if !independent {
+            lift.set_service(Service::Normal)?;
+            lift.set_doors_open(false)?;
+            return lift.log(format!("Lift {} back on group control", id));
+        }
+        lift.set_service(Service::Independent)?;
+        lift.log(format!("Lift {} on independent service", id))?;
+        for passenger in lift.take_waiting(|_| true)? {
+            if let Err(e) = self.assign(passenger.clone()) {
+                self.give_up(&passenger, &e)?;
+            }
+        }
+        if lift.next_target().is_err() {
+            lift.set_doors_open(true)?;
+        }
+        Ok(())
+    }
+
+    /// The lift on firefighter service, if there is one.
+    pub fn firefighter_car(&self) -> Result<Option<u32>, String> {
+        for lift in self.banks.iter().flat_map(|b| &b.lifts) {
+            if lift.service()? == Service::Firefighter {
+                return Ok(Some(lift.id()?));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Send a lift to a floor from inside the car, as a firefighter, a
+    /// lift attendant or a passenger pressing the wrong button might. During
+    /// a fire recall, the first lift to get a car call is put on firefighter
+    /// service, and is then the only one that will take them.
+    pub fn car_call(&self, id: u32, floor: i32) -> Result<(), String> {
+        let (bank, lift) = self.find(id)?;
+        if !bank.zone.serves(floor) {
+            return Err(format!("Lift {} does not serve floor {}.", id, floor));
+        }
+        match lift.service()? {
+            Service::Normal => return lift.press(floor),
+            Service::Firefighter | Service::Independent => {}
+            Service::Recall(_) if self.firefighter_car()?.is_none() => {
+                lift.set_service(Service::Firefighter)?;
+            }
+            Service::Recall(_) => return Err(format!("Lift {} is not taking car calls.", id)),
+        }
+        // Doors held open for the firefighter or attendant close on command.
+        lift.set_doors_open(false)?;
+        lift.press(floor)
+    }
+
+    pub fn cancel_car_call(&self, id: u32, floor: i32) -> Result<(), String> {
+        self.lift(id)?.unpress(floor)
+    }
+
+    /// Take one passenger waiting to go from `from_floor` to `to_floor` off
+    /// whichever lift they were assigned to, returning them and the lift.
+    fn take_call(&self, from_floor: i32, to_floor: i32) -> Result<(Passenger, &Bank, u32), String> {
+        for bank in &self.banks {
+            for lift in &bank.lifts {
+                let mut found = false;
+                let taken = lift.take_waiting(|p| {
+                    let matches = !found && p.from_floor == from_floor && p.to_floor == to_floor;
+                    found |= matches;
+                    matches
+                })?;
+                if let Some(passenger) = taken.into_iter().next() {
+                    return Ok((passenger, bank, lift.id()?));
+                }
+            }
+        }
+        Err(format!(
+            "No one is waiting to go from floor {} to floor {}.",
+            from_floor, to_floor
+        ))
+    }
+
+    /// Cancel a call that has not yet been picked up, as when someone calls
+    /// a lift and then walks away.
+    pub fn cancel_call(&self, from_floor: i32, to_floor: i32) -> Result<(), String> {
+        let mut found = false;
+        let held = self.take_held(|p| {
+            let matches = !found && p.from_floor == from_floor && p.to_floor == to_floor;
+            found |= matches;
+            matches
+        })?;
+        if !held.is_empty() {
+            return Ok(());
+        }
+        self.take_call(from_floor, to_floor).map(|_| ())
+    }
+
+    /// A waiting passenger gets into the wrong car: another lift in the
+    /// same bank, whichever is nearest, ends up carrying them instead. If
+    /// no other lift is in service the passenger keeps waiting for theirs.
+    pub fn wrong_car(&self, from_floor: i32, to_floor: i32) -> Result<u32, String> {
+        let (passenger, bank, assigned) = self.take_call(from_floor, to_floor)?;
+        let mut nearest = None;
+        let mut shortest = i32::MAX;
+        for lift in &bank.lifts {
+            if lift.id()? == assigned
+                || lift.fault()?.is_some()
+                || lift.service()? != Service::Normal
+            {
+                continue;
+            }
+            let distance = lift.distance_from(&passenger)?;
+            if distance < shortest {
+                shortest = distance;
+                nearest = Some(lift);
+            }
+        }
+        let Some(lift) = nearest else {
+            self.lift(assigned)?.add_passenger(passenger)?;
+            return Err(format!(
+                "There is no other lift to get into at floor {}.",
+                from_floor
+            ));
+        };
+        lift.add_passenger(passenger)?;
+        lift.id()
+    }
+
+    fn log_call(&self, passenger: &Passenger) -> Result<(), String> {
+        let mut calls = self
+            .calls
+            .write()
+            .map_err(|e| format!("Failed to write-lock calls: {}", e))?;
+        calls.record(passenger.from_floor, passenger.to_floor);
+        Ok(())
+    }
+
+    /// Floors that recent calls have come from, busiest first.
+    fn demand(&self) -> Result<Vec<i32>, String> {
+        let calls = self
+            .calls
+            .read()
+            .map_err(|e| format!("Failed to read-lock calls: {}", e))?;
+        Ok(calls.demand())
+    }
+
+    fn park(&self) -> Result<(), String> {
+        let parking = match self.mode()?.0 {
+            Mode::UpPeak => Parking::Lobby,
+            Mode::DownPeak => Parking::Upper,
+            Mode::Balanced => *self
+                .parking
+                .read()
+                .map_err(|e| format!("Failed to read-lock parking: {}", e))?,
+        };
+        if parking == Parking::Stay {
+            return Ok(());
+        }
+        let demand = self.demand()?;
+        for bank in &self.banks {
+            bank.park(parking, &demand)?;
+        }
+        Ok(())
+    }
+
+    /// Route a new passenger through whichever sky lobbies they need to
+    /// reach their destination.
+    fn plan(&self, passenger: Passenger) -> Result<Passenger, String> {
+        let destination = passenger.to_floor;
+        let zones: Vec<Zone> = self.banks.iter().map(|b| b.zone).collect();
+        match route(&zones, passenger.from_floor, destination) {
+            Some(itinerary) => Ok(Passenger {
+                to_floor: itinerary[1],
+                itinerary,
+                ..passenger
+            }),
+            None => Err(format!(
+                "No route from floor {} to floor {}.",
+                passenger.from_floor, destination
+            )),
+        }
+    }
+
+    /// Send a passenger let off at a sky lobby on to their next lift, or
+    /// log their journey if they have arrived. Anyone whose next leg cannot
+    /// be served, as during a fire recall, gives up there.
+    fn arrive(&self, passenger: Passenger) -> Result<(), String> {
+        let Some(leg) = passenger.next_leg() else {
+            return self.complete(passenger);
+        };
+        if let Err(e) = self.respond(leg.clone()) {
+            self.give_up(&leg, &e)?;
+        }
+        Ok(())
+    }
+
+    fn complete(&self, passenger: Passenger) -> Result<(), String> {
+        let mut journeys = self
+            .journeys
+            .write()
+            .map_err(|e| format!("Failed to write-lock journeys: {}", e))?;
+        let boarded_at = passenger.boarded_at.unwrap_or(passenger.called_at);
+        let journey = Journey {
+            legs: passenger.itinerary.len() - 1,
+            priority: passenger.priority,
+            time: passenger.called_at.elapsed(),
+            wait: boarded_at - passenger.called_at,
+            finished: Instant::now(),
+        };
+        let message = format!(
+            "Passenger #{} arrived at {} after {:.1}s",
+            passenger.id,
+            passenger.to_floor,
+            journey.time.as_secs_f64()
+        );
+        journeys.extend(vec![journey; passenger.group]);
+        drop(journeys);
+        self.log(None, message)
+    }
+
+    /// The number of completed journeys, how many of them changed lifts,
+    /// and their average time.
+    pub fn journey_summary(&self) -> Result<(usize, usize, Duration), String> {
+        let journeys = self
+            .journeys
+            .read()
+            .map_err(|e| format!("Failed to read-lock journeys: {}", e))?;
+        if journeys.is_empty() {
+            return Ok((0, 0, Duration::ZERO));
+        }
+        let transfers = journeys.iter().filter(|j| j.legs > 1).count();
+        let total: Duration = journeys.iter().map(|j| j.time).sum();
+        Ok((journeys.len(), transfers, total / journeys.len() as u32))
+    }
+
+    /// The number of completed journeys in each priority class, and their
+    /// average and longest times.
+    pub fn class_summary(&self) -> Result<Vec<(Priority, usize, Duration, Duration)>, String> {
+        let journeys = self
+            .journeys
+            .read()
+            .map_err(|e| format!("Failed to read-lock journeys: {}", e))?;
+        let mut classes: Vec<Priority> = journeys.iter().map(|j| j.priority).collect();
+        classes.sort();
+        classes.dedup();
+        Ok(classes
+            .into_iter()
+            .map(|class| {
+                let times: Vec<Duration> = journeys
+                    .iter()
+                    .filter(|j| j.priority == class)
+                    .map(|j| j.time)
+                    .collect();
+                let total: Duration = times.iter().sum();
+                let longest = times.iter().max().copied().unwrap_or_default();
+                (class, times.len(), total / times.len() as u32, longest)
+            })
+            .collect())
+    }
+
+    /// A full snapshot of one lift, for the side panel.
+    pub fn detail(&self, id: u32) -> Result<Detail, String> {
+        let (bank, lift) = self.find(id)?;
+        lift.detail(&bank.name)
+    }
+
+    /// Live figures on the people waiting and riding, recent journeys and
+    /// how full each lift is, keeping the waits of the last `recent`
+    /// journeys.
+    pub fn stats(&self, recent: usize) -> Result<Stats, String> {
+        let mut stats = Stats::default();
+        let mut total_wait = Duration::ZERO;
+        for bank in &self.banks {
+            for lift in &bank.lifts {
+                let passengers = lift
+                    .passengers
+                    .read()
+                    .map_err(|e| format!("Failed to read-lock passengers: {}", e))?;
+                for passenger in passengers.iter() {
+                    if passenger.riding {
+                        stats.riding += passenger.group;
+                    } else {
+                        let wait = passenger.called_at.elapsed();
+                        stats.waiting += passenger.group;
+                        total_wait += wait * passenger.group as u32;
+                        stats.longest_wait = stats.longest_wait.max(wait);
+                    }
+                }
+                let assigned: usize = passengers.iter().map(|p| p.group).sum();
+                let capacity = lift.energy.capacity().max(1);
+                let share = (assigned as f64 / capacity as f64).min(1.0);
+                stats.utilisation.push((lift.id()?, share));
+            }
+        }
+        let held = self
+            .held
+            .read()
+            .map_err(|e| format!("Failed to read-lock held: {}", e))?;
+        for passenger in held.iter() {
+            let wait = passenger.called_at.elapsed();
+            stats.waiting += passenger.group;
+            total_wait += wait * passenger.group as u32;
+            stats.longest_wait = stats.longest_wait.max(wait);
+        }
+        drop(held);
+        if stats.waiting > 0 {
+            stats.average_wait = total_wait / stats.waiting as u32;
+        }
+        let journeys = self
+            .journeys
+            .read()
+            .map_err(|e| format!("Failed to read-lock journeys: {}", e))?;
+        stats.throughput = journeys
+            .iter()
+            .rev()
+            .take_while(|j| j.finished.elapsed() <= THROUGHPUT_WINDOW)
+            .count();
+        let start = journeys.len().saturating_sub(recent);
+        stats.waits = journeys[start..].iter().map(|j| j.wait).collect();
+        Ok(stats)
+    }
+
+    /// Each lift's id and energy meter.
+    pub fn energy_report(&self) -> Result<Vec<(u32, Meter)>, String> {
+        let mut report = Vec::new();
+        for bank in &self.banks {
+            for lift in &bank.lifts {
+                report.push((lift.id()?, lift.meter()?));
+            }
+        }
+        Ok(report)
+    }
+
+    pub fn set_seed(&self, seed: u64) -> Result<(), String> {
+        let mut rng = self
+            .rng
+            .write()
+            .map_err(|e| format!("Failed to write-lock rng: {}", e))?;
+        *rng = StdRng::seed_from_u64(seed);
+        Ok(())
     }
 
     pub fn random(&self) {
         let mut floors: Vec<i32> = (self.bottom_floor..self.top_floor).collect();
-        floors.shuffle(&mut thread_rng());
+        let Ok(mut rng) = self.rng.write() else {
+            return;
+        };
+        floors.shuffle(&mut *rng);
+        drop(rng);
         // let from = floors.pop().unwrap();
         // let to = floors.pop().unwrap();
         let _ = self.respond(Passenger::new(floors[0], floors[1]));
     }
 
-    pub fn realistic_random(&self) {
-        let mut rng = rand::thread_rng();
-        let rand = rng.gen_range(self.bottom_floor..self.top_floor);
-        let mut floors = vec![0, rand];
-        floors.shuffle(&mut thread_rng());
-        let _ = self.respond(Passenger::new(floors[0], floors[1]));
+    pub fn realistic_random(&self) {
+        let Ok(mut rng) = self.rng.write() else {
+            return;
+        };
+        let rand = rng.gen_range(self.bottom_floor..self.top_floor);
+        let mut floors = [0, rand];
+        floors.shuffle(&mut *rng);
+        drop(rng);
+        let _ = self.respond(Passenger::new(floors[0], floors[1]));
+    }
+
+    pub fn debug(&self) {
+        eprintln!("{:?}", self);
+    }
+}
+
+/// A snapshot of a lift for drawing.
+#[derive(Clone, Debug)]
+pub struct Car {
+    pub id: u32,
+    /// The floor of the lower deck.
+    pub floor: i32,
+    pub decks: i32,
+    pub doors_open: bool,
+    pub fault: Option<Fault>,
+    /// The arrow, door or fault symbol shown on the car.
+    pub symbol: char,
+}
+
+/// Everything about one lift: where it is going, who it is carrying and
+/// who it is on its way to collect.
+#[derive(Clone, Debug)]
+pub struct Detail {
+    pub car: Car,
+    pub bank: String,
+    pub direction: Direction,
+    /// Who the lift is taking orders from, if not its dispatcher.
+    pub service: Option<String>,
+    pub next_target: Option<i32>,
+    pub targets: Vec<i32>,
+    pub riding: Vec<Passenger>,
+    /// Passengers assigned to the lift and still waiting for it.
+    pub assigned: Vec<Passenger>,
+    pub trips: u32,
+    pub floors: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Up,
+    Down,
+    Stopped,
+}
+
+/// Something wrong with a lift that keeps it from taking calls.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// Taken out of service: the lift lets off the passengers it has
+    /// aboard and then stays where it is.
+    OutOfService,
+    /// Stuck where it is, between floors if it was moving, with its
+    /// passengers aboard.
+    Breakdown,
+    /// The doors will not open, so no one can get in or out.
+    Doors,
+}
+
+impl FromStr for Fault {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "out" | "out-of-service" => Ok(Fault::OutOfService),
+            "breakdown" => Ok(Fault::Breakdown),
+            "doors" => Ok(Fault::Doors),
+            _ => Err(format!(
+                "Invalid fault '{}': expected out, breakdown or doors",
+                s
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for Fault {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Fault::OutOfService => write!(f, "out of service"),
+            Fault::Breakdown => write!(f, "broken down"),
+            Fault::Doors => write!(f, "doors stuck"),
+        }
+    }
+}
+
+/// Who a lift is taking orders from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Service {
+    /// Answering calls assigned by its bank's dispatcher.
+    Normal,
+    /// Recalled for a fire: going nonstop to this floor, then waiting there
+    /// with its doors open.
+    Recall(i32),
+    /// Driven by a firefighter, answering car calls only.
+    Firefighter,
+    /// Out of group control, answering car calls only.
+    Independent,
+}
+
+/// What a lift is doing when it has no passengers to serve.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Idle {
+    Busy,
+    Since(Instant),
+    Parking(i32),
+    Parked,
+    /// Heading down to the lobby from the floor it was parked at, in
+    /// down-peak.
+    Sweeping,
+}
+
+#[derive(Debug)]
+struct Lift {
+    id: RwLock<u32>,
+    lobby: i32,
+    /// The floors the lift's shaft runs between.
+    zone: Zone,
+    decks: i32,
+    energy: EnergyModel,
+    mode: Arc<RwLock<Mode>>,
+    meter: RwLock<Meter>,
+    /// The floor of the lower deck.
+    floor: RwLock<i32>,
+    direction: RwLock<Direction>,
+    doors_open: RwLock<bool>,
+    passengers: RwLock<Vec<Passenger>>,
+    /// Floors pressed inside the car that no passenger is known to need,
+    /// such as a firefighter's stops or a wrong button.
+    car_calls: RwLock<Vec<i32>>,
+    idle: RwLock<Idle>,
+    fault: RwLock<Option<Fault>>,
+    service: RwLock<Service>,
+    log: Arc<RwLock<EventLog>>,
+    handoff: Sender<Passenger>,
+}
+
+const MS_PER_FLOOR: u64 = 500;
+const DOOR_OPEN_TIME: u64 = 750;
+const ACCESSIBLE_DOOR_OPEN_TIME: u64 = 2000;
+
+impl Lift {
+    fn new(
+        id: u32,
+        lobby: i32,
+        decks: i32,
+        energy: EnergyModel,
+        mode: Arc<RwLock<Mode>>,
+        log: Arc<RwLock<EventLog>>,
+        handoff: Sender<Passenger>,
+    ) -> Lift {
+        Lift {
+            id: RwLock::new(id),
+            lobby,
+            zone: Zone {
+                lowest: i32::MIN,
+                highest: i32::MAX,
+            },
+            decks,
+            energy,
+            mode,
+            meter: RwLock::new(Meter::default()),
+            floor: RwLock::new(lobby),
+            direction: RwLock::new(Direction::Stopped),
+            doors_open: RwLock::new(false),
+            passengers: RwLock::new(Vec::new()),
+            car_calls: RwLock::new(Vec::new()),
+            idle: RwLock::new(Idle::Since(Instant::now())),
+            fault: RwLock::new(None),
+            service: RwLock::new(Service::Normal),
+            log,
+            handoff,
+        }
+    }
+
+    /// Confine the lift to a zone, starting it at the lobby or as near to
+    /// it as every deck can reach.
+    fn with_zone(self, zone: Zone) -> Lift {
+        let lift = Lift { zone, ..self };
+        let floor = lift.reachable(lift.lobby);
+        Lift {
+            floor: RwLock::new(floor),
+            ..lift
+        }
+    }
+
+    fn id(&self) -> Result<u32, String> {
+        let id = *self
+            .id
+            .read()
+            .map_err(|e| format!("Failed to read-lock id: {}", e))?;
+        Ok(id)
+    }
+
+    fn log(&self, message: String) -> Result<(), String> {
+        let id = self.id()?;
+        self.log
+            .write()
+            .map_err(|e| format!("Failed to write-lock log: {}", e))?
+            .push(Some(id), message);
+        Ok(())
+    }
+
+    /// The deck a passenger should ride in. In a double-deck car the lower
+    /// deck serves floors an even number of storeys from the lobby and the
+    /// upper deck the rest, so passengers leaving the lobby choose their
+    /// deck by destination and everyone else by where they get on.
+    fn deck_for(&self, passenger: &Passenger) -> i32 {
+        if self.decks == 1 {
+            return 0;
+        }
+        let floor = if passenger.from_floor == self.lobby {
+            passenger.to_floor
+        } else {
+            passenger.from_floor
+        };
+        let preferred = (floor - self.lobby).rem_euclid(self.decks);
+        // At the ends of the zone only one deck can reach some floors.
+        let fits = |deck: i32| {
+            [passenger.from_floor, passenger.to_floor]
+                .iter()
+                .all(|f| *f == self.lobby || self.reachable(f - deck) == f - deck)
+        };
+        (0..self.decks)
+            .map(|d| (preferred + d) % self.decks)
+            .find(|deck| fits(*deck))
+            .unwrap_or(preferred)
+    }
+
+    /// Where the lower deck must stop for a passenger on the given deck to
+    /// use the given floor. Both decks load together at the lobby, which
+    /// has a landing for each.
+    fn stop_for(&self, floor: i32, deck: i32) -> i32 {
+        if floor == self.lobby {
+            self.reachable(floor)
+        } else {
+            self.reachable(floor - deck)
+        }
+    }
+
+    /// The nearest floor to `floor` that the lower deck can stop at with
+    /// every deck still inside the zone.
+    fn reachable(&self, floor: i32) -> i32 {
+        let highest = (self.zone.highest - (self.decks - 1)).max(self.zone.lowest);
+        floor.clamp(self.zone.lowest, highest)
+    }
+
+    fn meter(&self) -> Result<Meter, String> {
+        let meter = *self
+            .meter
+            .read()
+            .map_err(|e| format!("Failed to read-lock meter: {}", e))?;
+        Ok(meter)
+    }
+
+    fn update_meter(&self, update: impl FnOnce(&mut Meter)) -> Result<(), String> {
+        let mut meter = self
+            .meter
+            .write()
+            .map_err(|e| format!("Failed to write-lock meter: {}", e))?;
+        update(&mut meter);
+        Ok(())
+    }
+
+    fn idle(&self) -> Result<Idle, String> {
+        let idle = *self
+            .idle
+            .read()
+            .map_err(|e| format!("Failed to read-lock idle: {}", e))?;
+        Ok(idle)
+    }
+
+    fn set_idle(&self, new_idle: Idle) -> Result<(), String> {
+        let mut idle = self
+            .idle
+            .write()
+            .map_err(|e| format!("Failed to write-lock idle: {}", e))?;
+        *idle = new_idle;
+        Ok(())
+    }
+
+    fn fault(&self) -> Result<Option<Fault>, String> {
+        let fault = *self
+            .fault
+            .read()
+            .map_err(|e| format!("Failed to read-lock fault: {}", e))?;
+        Ok(fault)
+    }
+
+    fn set_fault(&self, new_fault: Option<Fault>) -> Result<(), String> {
+        let mut fault = self
+            .fault
+            .write()
+            .map_err(|e| format!("Failed to write-lock fault: {}", e))?;
+        *fault = new_fault;
+        drop(fault);
+        self.set_idle(Idle::Since(Instant::now()))
+    }
+
+    /// Remove the passengers who have not yet got on and match `filter`,
+    /// leaving only the stops needed by everyone else.
+    fn take_waiting(
+        &self,
+        mut filter: impl FnMut(&Passenger) -> bool,
+    ) -> Result<Vec<Passenger>, String> {
+        let mut passengers = self
+            .passengers
+            .write()
+            .map_err(|e| format!("Failed to write-lock passengers: {}", e))?;
+        let (kept, waiting): (Vec<Passenger>, Vec<Passenger>) =
+            passengers.drain(..).partition(|p| p.riding || !filter(p));
+        *passengers = kept;
+        Ok(waiting
+            .into_iter()
+            .map(|p| Passenger { deck: 0, ..p })
+            .collect())
+    }
+
+    /// The floors the lift has to stop at, in order: wherever someone is
+    /// waiting for it or wants to get out, and any car calls. Nothing else
+    /// is kept, so once nobody needs a stop it is gone.
+    fn targets(&self) -> Result<Vec<i32>, String> {
+        let passengers = self
+            .passengers
+            .read()
+            .map_err(|e| format!("Failed to read-lock passengers: {}", e))?;
+        let car_calls = self
+            .car_calls
+            .read()
+            .map_err(|e| format!("Failed to read-lock car_calls: {}", e))?;
+        Ok(self.stops(&passengers, &car_calls))
+    }
+
+    fn stops(&self, passengers: &[Passenger], car_calls: &[i32]) -> Vec<i32> {
+        let mut stops = car_calls.to_vec();
+        for passenger in passengers {
+            let floor = if passenger.riding {
+                passenger.to_floor
+            } else {
+                passenger.from_floor
+            };
+            binary_add(&mut stops, self.stop_for(floor, passenger.deck));
+        }
+        stops
+    }
+
+    /// Press the button for a floor inside the car.
+    fn press(&self, floor: i32) -> Result<(), String> {
+        let mut car_calls = self
+            .car_calls
+            .write()
+            .map_err(|e| format!("Failed to write-lock car_calls: {}", e))?;
+        binary_add(&mut car_calls, self.reachable(floor));
+        self.set_idle(Idle::Busy)
+    }
+
+    /// Cancel a car call. The lift still stops there if a passenger needs
+    /// it to.
+    fn unpress(&self, floor: i32) -> Result<(), String> {
+        let mut car_calls = self
+            .car_calls
+            .write()
+            .map_err(|e| format!("Failed to write-lock car_calls: {}", e))?;
+        let Ok(pos) = car_calls.binary_search(&self.reachable(floor)) else {
+            return Err(format!("There is no car call for floor {}.", floor));
+        };
+        car_calls.remove(pos);
+        Ok(())
+    }
+
+    fn service(&self) -> Result<Service, String> {
+        let service = *self
+            .service
+            .read()
+            .map_err(|e| format!("Failed to read-lock service: {}", e))?;
+        Ok(service)
+    }
+
+    fn set_service(&self, new_service: Service) -> Result<(), String> {
+        let mut service = self
+            .service
+            .write()
+            .map_err(|e| format!("Failed to write-lock service: {}", e))?;
+        *service = new_service;
+        Ok(())
+    }
+
+    /// Head nonstop for the recall floor, and return everyone who was still
+    /// waiting for the lift.
+    fn recall(&self, floor: i32) -> Result<Vec<Passenger>, String> {
+        let waiting = self.take_waiting(|_| true)?;
+        let mut car_calls = self
+            .car_calls
+            .write()
+            .map_err(|e| format!("Failed to write-lock car_calls: {}", e))?;
+        car_calls.clear();
+        drop(car_calls);
+        self.set_service(Service::Recall(self.reachable(floor)))?;
+        self.set_idle(Idle::Since(Instant::now()))?;
+        Ok(waiting)
+    }
+
+    /// One step of a recall: move towards the recall floor or, once there,
+    /// let everyone off, their journeys ending there, and keep the doors
+    /// open.
+    fn return_to(&self, floor: i32) -> Result<(i32, Direction, bool), String> {
+        let (current, _, _) = self.get_info()?;
+        if current != floor {
+            return self.move_towards(floor);
+        }
+        let mut passengers = self
+            .passengers
+            .write()
+            .map_err(|e| format!("Failed to write-lock passengers: {}", e))?;
+        let alighted: Vec<Passenger> = passengers.drain(..).collect();
+        drop(passengers);
+        self.set_direction(Direction::Stopped)?;
+        self.update_meter(Meter::stop)?;
+        let info = self.set_doors_open(true)?;
+        let id = self.id()?;
+        for passenger in alighted {
+            self.log(format!(
+                "Passenger #{} got out of lift {} at {}",
+                passenger.id, id, floor
+            ))?;
+            if let Err(e) = self.handoff.send(passenger.end_at(floor)) {
+                return Err(format!("Lift {}: Failed to hand off passenger: {}", id, e));
+            }
+        }
+        Ok(info)
+    }
+
+    fn park(&self, floor: i32) -> Result<(), String> {
+        let floor = self.reachable(floor);
+        self.set_idle(Idle::Parking(floor))?;
+        let (current, _, _) = self.get_info()?;
+        if floor == current {
+            self.parking_target()?;
+        }
+        Ok(())
     }
 
-    fn best_lift(&self, passenger: &Passenger) -> Result<usize, String> {
-        let mut best = 0;
-        let mut closest = i32::MAX;
-        let lifts = &self.lifts;
-        let mut indices: Vec<usize> = (0..lifts.len()).collect();
-        indices.shuffle(&mut thread_rng());
-        for index in indices {
-            let lift = &lifts[index];
-            if let Ok(dist) = lift.distance_from(passenger) {
-                if dist < closest {
-                    closest = dist;
-                    best = index;
-                }
+    /// Called when the lift has no targets left: note when it went idle,
+    /// and return the floor it is being parked at, or swept down to in
+    /// down-peak, if it is still on its way there.
+    fn parking_target(&self) -> Result<Option<i32>, String> {
+        let (floor, _, _) = self.get_info()?;
+        let lobby = self.reachable(self.lobby);
+        let mode = *self
+            .mode
+            .read()
+            .map_err(|e| format!("Failed to read-lock mode: {}", e))?;
+        match self.idle()? {
+            Idle::Busy => self.set_idle(Idle::Since(Instant::now()))?,
+            Idle::Parking(target)
+                if target == floor && mode == Mode::DownPeak && floor != lobby =>
+            {
+                self.set_idle(Idle::Sweeping)?;
+                return Ok(Some(lobby));
             }
+            Idle::Parking(target) if target == floor => self.set_idle(Idle::Parked)?,
+            Idle::Parking(target) => return Ok(Some(target)),
+            // Back at the lobby, the lift waits to be sent up again.
+            Idle::Sweeping if floor == lobby => self.set_idle(Idle::Since(Instant::now()))?,
+            Idle::Sweeping => return Ok(Some(lobby)),
+            Idle::Since(_) | Idle::Parked => {}
         }
-        Ok(best)
+        Ok(None)
     }
 
-    pub fn debug(&self) {
-        eprintln!("{:?}", self);
+    fn has_passengers(&self) -> Result<bool, String> {
+        let passengers = self
+            .passengers
+            .read()
+            .map_err(|e| format!("Failed to read-lock passengers: {}", e))?;
+        Ok(!passengers.is_empty())
     }
-}
-
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub enum Direction {
-    Up,
-    Down,
-    Stopped,
-}
 
-#[derive(Debug)]
-struct Lift {
-    id: RwLock<u32>,
-    floor: RwLock<i32>,
-    direction: RwLock<Direction>,
-    doors_open: RwLock<bool>,
-    passengers: RwLock<Vec<Passenger>>,
-    targets: RwLock<Vec<i32>>,
-}
+    /// Whether the lift is serving a priority passenger, and so taking no
+    /// one else.
+    fn exclusive(&self) -> Result<bool, String> {
+        let passengers = self
+            .passengers
+            .read()
+            .map_err(|e| format!("Failed to read-lock passengers: {}", e))?;
+        Ok(passengers.iter().any(|p| p.priority != Priority::Normal))
+    }
 
-const MS_PER_FLOOR: u64 = 500;
-const DOOR_OPEN_TIME: u64 = 750;
+    fn occupants(&self) -> Result<usize, String> {
+        let passengers = self
+            .passengers
+            .read()
+            .map_err(|e| format!("Failed to read-lock passengers: {}", e))?;
+        Ok(passengers
+            .iter()
+            .filter(|p| p.riding)
+            .map(|p| p.group)
+            .sum())
+    }
 
-impl Lift {
-    fn new(id: u32) -> Lift {
-        Lift {
-            id: RwLock::new(id),
-            floor: RwLock::new(0),
-            direction: RwLock::new(Direction::Stopped),
-            doors_open: RwLock::new(false),
-            passengers: RwLock::new(Vec::new()),
-            targets: RwLock::new(Vec::new()),
-        }
+    /// How many more people the car can be given, counting those still
+    /// waiting for it.
+    fn room(&self) -> Result<usize, String> {
+        let passengers = self
+            .passengers
+            .read()
+            .map_err(|e| format!("Failed to read-lock passengers: {}", e))?;
+        let assigned: usize = passengers.iter().map(|p| p.group).sum();
+        Ok(self.energy.capacity().saturating_sub(assigned))
     }
 
     fn get_info(&self) -> Result<(i32, Direction, bool), String> {
@@ -181,19 +2151,57 @@ impl Lift {
         Ok((floor, direction, doors_open))
     }
 
+    /// Do whatever the lift has to do next: move a floor towards its next
+    /// target or the floor it is being parked at, or stay where it is.
+    fn step(&self) -> Result<(), String> {
+        if let Some(Fault::Breakdown | Fault::Doors) = self.fault()? {
+            self.update_meter(Meter::stop)?;
+        } else if let Service::Recall(floor) = self.service()? {
+            self.return_to(floor)?;
+        } else if let Some(target) = self.next_move()? {
+            self.move_towards(target)?;
+        } else {
+            self.set_direction(Direction::Stopped)?;
+            self.update_meter(Meter::stop)?;
+        }
+        Ok(())
+    }
+
+    /// Where the lift should head next, if anywhere. A lift on independent
+    /// service holds its doors open wherever it stopped until it is given
+    /// another command, even if it still has car calls.
+    fn next_move(&self) -> Result<Option<i32>, String> {
+        let (_, _, doors_open) = self.get_info()?;
+        if doors_open && self.service()? == Service::Independent {
+            return Ok(None);
+        }
+        match self.next_target() {
+            Ok(target) => Ok(Some(target)),
+            Err(_) => self.parking_target(),
+        }
+    }
+
     fn move_towards(&self, target: i32) -> Result<(i32, Direction, bool), String> {
-        // let id = *self
-        //     .id
-        //     .read()
-        //     .map_err(|e| format!("Failed to read-lock id: {}", e))?;
         let (floor, direction, _) = self.get_info()?;
         if target > floor {
             self.set_direction(Direction::Up)?;
         } else if target < floor {
             self.set_direction(Direction::Down)?;
         }
-        // println!("Lift {}: On floor {}, going to {}.", id, floor, target);
         wait_millis(MS_PER_FLOOR);
+        if self.fault()? == Some(Fault::Breakdown) {
+            // Stuck between floors.
+            return self.get_info();
+        }
+        let step = match direction {
+            Direction::Up => 1,
+            Direction::Down => -1,
+            Direction::Stopped => 0,
+        };
+        if step != 0 {
+            let occupants = self.occupants()?;
+            self.update_meter(|m| m.travel(&self.energy, step, occupants))?;
+        }
         match direction {
             Direction::Up => self.reach_floor(floor + 1)?,
             Direction::Down => self.reach_floor(floor - 1)?,
@@ -232,91 +2240,166 @@ impl Lift {
             .doors_open
             .write()
             .map_err(|e| format!("Failed to write-lock doors_opening: {}", e))?;
+        let opening = status && !*doors_open;
         *doors_open = status;
         drop(doors_open);
-        self.get_info()
+        let info = self.get_info()?;
+        if opening {
+            self.log(format!("Lift {} doors open at {}", self.id()?, info.0))?;
+        }
+        Ok(info)
     }
 
-    fn open_doors(&self) -> Result<(i32, Direction, bool), String> {
-        // let id = *self
-        //     .id
-        //     .read()
-        //     .map_err(|e| format!("Failed to read-lock id: {}", e))?;
-        // let (floor, _) = self.get_info()?;
-        // println!("Lift {}: Doors opening on floor {}.", id, floor);
+    fn open_doors(&self, dwell: u64) -> Result<(i32, Direction, bool), String> {
         self.set_doors_open(true)?;
-        wait_millis(DOOR_OPEN_TIME);
-        // println!("Lift {}: Doors closing on floor {}.", id, floor);
-        wait_millis(DOOR_OPEN_TIME);
+        self.update_meter(Meter::stop)?;
+        wait_millis(dwell);
+        wait_millis(dwell);
         self.set_doors_open(false)?;
         self.get_info()
     }
 
-    fn add_target(&self, target: i32) -> Result<(i32, Direction, bool), String> {
-        let mut targets = self
-            .targets
-            .write()
-            .map_err(|e| format!("Failed to write-lock targets: {}", e))?;
-        binary_add(&mut targets, target);
-        drop(targets);
-        self.get_info()
-    }
-
     fn reach_floor(&self, new_floor: i32) -> Result<(i32, Direction, bool), String> {
         self.set_floor(new_floor)?;
+        if self.fault()? == Some(Fault::Doors) {
+            return self.get_info();
+        }
+        if let Service::Recall(_) = self.service()? {
+            // Nonstop to the recall floor.
+            return self.get_info();
+        }
         let mut passengers = self
             .passengers
             .write()
             .map_err(|e| format!("Failed to write-lock passengers: {}", e))?;
-        let mut targets = self
-            .targets
+        let mut car_calls = self
+            .car_calls
             .write()
-            .map_err(|e| format!("Failed to write-lock targets: {}", e))?;
-        let mut open_doors = false;
-        if let Ok(pos) = targets.binary_search(&new_floor) {
-            targets.remove(pos);
-            open_doors = true;
+            .map_err(|e| format!("Failed to write-lock car_calls: {}", e))?;
+        // Only stop if someone is getting in or out, or asked for the floor.
+        let open_doors = self.stops(&passengers, &car_calls).contains(&new_floor);
+        if let Ok(pos) = car_calls.binary_search(&new_floor) {
+            car_calls.remove(pos);
         }
-        drop(targets);
+        drop(car_calls);
         let mut to_remove: Vec<usize> = vec![];
+        let mut boarded: Vec<u64> = vec![];
+        let mut dwell = DOOR_OPEN_TIME;
         for i in 0..passengers.len() {
             let passenger = &mut passengers[i];
-            if passenger.from_floor == new_floor {
+            let boarding = self.stop_for(passenger.from_floor, passenger.deck) == new_floor;
+            if boarding && !passenger.riding {
                 passenger.riding = true;
-                self.add_target(passenger.to_floor)?;
+                passenger.boarded_at.get_or_insert_with(Instant::now);
+                boarded.push(passenger.id);
             }
-            if passenger.to_floor == new_floor && passenger.riding {
+            if self.stop_for(passenger.to_floor, passenger.deck) == new_floor && passenger.riding {
                 to_remove.push(i);
+            } else if !boarding {
+                continue;
+            }
+            if passenger.priority == Priority::Accessibility {
+                dwell = ACCESSIBLE_DOOR_OPEN_TIME;
             }
         }
-        for i in to_remove.iter().rev() {
-            passengers.remove(*i);
-        }
+        let alighted: Vec<Passenger> = to_remove
+            .iter()
+            .rev()
+            .map(|i| passengers.remove(*i))
+            .collect();
         drop(passengers);
+        // Open the doors before anyone is seen to get in or out.
+        if open_doors {
+            self.set_doors_open(true)?;
+        }
+        let id = self.id()?;
+        for passenger in boarded {
+            self.log(format!(
+                "Passenger #{} got into lift {} at {}",
+                passenger, id, new_floor
+            ))?;
+        }
+        for passenger in alighted {
+            self.log(format!(
+                "Passenger #{} got out of lift {} at {}",
+                passenger.id, id, new_floor
+            ))?;
+            if let Err(e) = self.handoff.send(passenger) {
+                return Err(format!("Lift {}: Failed to hand off passenger: {}", id, e));
+            }
+        }
         if open_doors {
-            self.open_doors()?;
+            let mode = *self
+                .mode
+                .read()
+                .map_err(|e| format!("Failed to read-lock mode: {}", e))?;
+            if self.service()? == Service::Independent {
+                self.set_doors_open(true)?;
+                self.update_meter(Meter::stop)?;
+            } else if mode == Mode::UpPeak && new_floor == self.lobby && !self.exclusive()? {
+                self.hold_at_lobby()?;
+            } else {
+                self.open_doors(dwell)?;
+            }
+        }
+        self.get_info()
+    }
+
+    /// Keep the doors open at the lobby until the car is full or the hold
+    /// time runs out, letting on anyone assigned to the lift meanwhile.
+    fn hold_at_lobby(&self) -> Result<(i32, Direction, bool), String> {
+        let held = Instant::now();
+        self.set_doors_open(true)?;
+        self.update_meter(Meter::stop)?;
+        wait_millis(DOOR_OPEN_TIME);
+        while held.elapsed() < LOBBY_HOLD && self.occupants()? < self.energy.capacity() {
+            wait_millis(100);
+            self.board(self.lobby)?;
         }
+        self.set_doors_open(false)?;
         self.get_info()
     }
 
+    /// Let on everyone waiting for this lift at the floor it is stopped at.
+    fn board(&self, floor: i32) -> Result<(), String> {
+        let mut passengers = self
+            .passengers
+            .write()
+            .map_err(|e| format!("Failed to write-lock passengers: {}", e))?;
+        for passenger in passengers.iter_mut() {
+            if !passenger.riding && self.stop_for(passenger.from_floor, passenger.deck) == floor {
+                passenger.riding = true;
+                passenger.boarded_at.get_or_insert_with(Instant::now);
+                self.log(format!(
+                    "Passenger #{} got into lift {} at {}",
+                    passenger.id,
+                    self.id()?,
+                    floor
+                ))?;
+            }
+        }
+        Ok(())
+    }
+
     fn add_passenger(&self, passenger: Passenger) -> Result<(i32, Direction, bool), String> {
         let mut passengers = self
             .passengers
             .write()
             .map_err(|e| format!("Failed to write-lock passengers: {}", e))?;
-        binary_add(&mut passengers, passenger);
-        self.add_target(passenger.from_floor)?;
+        let deck = self.deck_for(&passenger);
+        // Identical passengers are separate people, so unlike stops they are
+        // never merged.
+        let pos = passengers.partition_point(|p| *p < passenger);
+        passengers.insert(pos, Passenger { deck, ..passenger });
+        self.set_idle(Idle::Busy)?;
         drop(passengers);
         self.get_info()
     }
 
     fn next_target(&self) -> Result<i32, String> {
-        let targets = self
-            .targets
-            .read()
-            .map_err(|e| format!("Failed to read-lock targets: {}", e))?;
+        let targets = self.targets()?;
         if targets.is_empty() {
-            return Err(format!("There are no more targets."));
+            return Err("There are no more targets.".to_string());
         }
         let (floor, direction, _) = self.get_info()?;
         let pos = match targets.binary_search(&floor) {
@@ -333,14 +2416,14 @@ impl Lift {
         };
         if pos == targets.len() {
             self.set_direction(Direction::Down)?;
-            return Ok(targets[pos - 1]);
+            Ok(targets[pos - 1])
         } else if pos == 0 {
             self.set_direction(Direction::Up)?;
-            return Ok(targets[0]);
+            Ok(targets[0])
         } else if direction == Direction::Up {
-            return Ok(targets[pos]);
+            Ok(targets[pos])
         } else {
-            return Ok(targets[pos - 1]);
+            Ok(targets[pos - 1])
         }
     }
 
@@ -352,10 +2435,7 @@ impl Lift {
             Direction::Down
         };
         let (l_floor, l_dir, _) = self.get_info()?;
-        let targets = self
-            .targets
-            .read()
-            .map_err(|e| format!("Failed to read-lock targets: {}", e))?;
+        let targets = self.targets()?;
         if l_dir == Direction::Stopped || targets.is_empty() {
             return Ok(difference(l_floor, p_floor));
         }
@@ -373,8 +2453,55 @@ impl Lift {
         Ok(distance)
     }
 
-    fn label(&self) -> Result<String, String> {
-        let (floor, direction, doors_open) = self.get_info()?;
+    /// Estimate the extra energy needed to pick the passenger up and take
+    /// them to their floor, including the extra stop.
+    fn energy_cost(&self, passenger: &Passenger) -> Result<f64, String> {
+        let (floor, direction, _) = self.get_info()?;
+        let occupants = self.occupants()?;
+        let deck = self.deck_for(passenger);
+        let pickup = self.stop_for(passenger.from_floor, deck);
+        let dropoff = self.stop_for(passenger.to_floor, deck);
+        let mut joules = self.energy.travel(pickup - floor, occupants)
+            + self
+                .energy
+                .travel(dropoff - pickup, occupants + passenger.group)
+            + self.energy.start(occupants + passenger.group);
+        if direction == Direction::Stopped {
+            joules += self.energy.start(occupants);
+        }
+        Ok(joules)
+    }
+
+    /// Estimate the milliseconds until the passenger would reach their
+    /// floor, stopping wherever the lift already has to on the way, plus
+    /// the time their own new stops would cost everyone already assigned.
+    fn time_to_destination(&self, passenger: &Passenger) -> Result<f64, String> {
+        let (floor, _, _) = self.get_info()?;
+        let targets = self.targets()?;
+        let deck = self.deck_for(passenger);
+        let pickup = self.stop_for(passenger.from_floor, deck);
+        let dropoff = self.stop_for(passenger.to_floor, deck);
+        let floors = self.distance_from(passenger)? + difference(pickup, dropoff);
+        let between = |a: i32, b: i32| {
+            targets
+                .iter()
+                .filter(|t| a.min(b) < **t && **t < a.max(b))
+                .count()
+        };
+        let stops = between(floor, pickup) + between(pickup, dropoff) + 2;
+        let new_stops = [pickup, dropoff]
+            .iter()
+            .filter(|s| !targets.contains(s))
+            .count();
+        let others = self.energy.capacity() - self.room()?;
+        let stop = 2 * DOOR_OPEN_TIME;
+        let own = floors as u64 * MS_PER_FLOOR + stops as u64 * stop;
+        let delay = (new_stops * others) as u64 * stop;
+        Ok((own + delay) as f64)
+    }
+
+    fn symbol(&self) -> Result<char, String> {
+        let (_, direction, doors_open) = self.get_info()?;
         let mut symbol = match direction {
             Direction::Up => '↑',
             Direction::Down => '↓',
@@ -382,10 +2509,141 @@ impl Lift {
         };
         if doors_open {
             symbol = '↔';
-            // return Ok(format!("{} ↔", floor))
         }
-        Ok(format!("{} {}", floor, symbol))
+        match self.fault()? {
+            Some(Fault::Breakdown) => symbol = '✖',
+            Some(Fault::Doors) => symbol = '⊠',
+            Some(Fault::OutOfService) if direction == Direction::Stopped => symbol = '⊘',
+            _ => {}
+        }
+        Ok(symbol)
+    }
+
+    fn car(&self) -> Result<Car, String> {
+        let (floor, _, doors_open) = self.get_info()?;
+        Ok(Car {
+            id: self.id()?,
+            floor,
+            decks: self.decks,
+            doors_open,
+            fault: self.fault()?,
+            symbol: self.symbol()?,
+        })
+    }
+
+    fn detail(&self, bank: &str) -> Result<Detail, String> {
+        let (floor, direction, _) = self.get_info()?;
+        let (riding, assigned) = self
+            .passengers
+            .read()
+            .map_err(|e| format!("Failed to read-lock passengers: {}", e))?
+            .iter()
+            .cloned()
+            .partition(|p| p.riding);
+        let targets = self.targets()?;
+        // Where the lift heads next without turning the lift around, as
+        // next_target would.
+        let ahead = match direction {
+            Direction::Down => targets.iter().rev().find(|t| **t <= floor),
+            _ => targets.iter().find(|t| **t >= floor),
+        };
+        let next_target = match direction {
+            Direction::Down => ahead.or(targets.first()),
+            _ => ahead.or(targets.last()),
+        };
+        let service = match self.service()? {
+            Service::Normal => None,
+            Service::Recall(lobby) => Some(format!("recalled to {}", lobby)),
+            Service::Firefighter => Some("firefighter service".to_string()),
+            Service::Independent => Some("independent service".to_string()),
+        };
+        let meter = self.meter()?;
+        Ok(Detail {
+            car: self.car()?,
+            bank: bank.to_string(),
+            direction,
+            service,
+            next_target: next_target.copied(),
+            targets,
+            riding,
+            assigned,
+            trips: meter.trips,
+            floors: meter.floors,
+        })
+    }
+
+    fn label(&self) -> Result<String, String> {
+        let (floor, direction, _) = self.get_info()?;
+        let symbol = self.symbol()?;
+        let floor = match (self.fault()?, direction) {
+            (Some(Fault::Breakdown), Direction::Up) => format!("{}-{}", floor, floor + 1),
+            (Some(Fault::Breakdown), Direction::Down) => format!("{}-{}", floor - 1, floor),
+            _ if self.decks == 2 => format!("{}/{}", floor, floor + 1),
+            _ => floor.to_string(),
+        };
+        match self.service()? {
+            Service::Firefighter => Ok(format!("FF {} {}", floor, symbol)),
+            Service::Independent => Ok(format!("IND {} {}", floor, symbol)),
+            _ => Ok(format!("{} {}", floor, symbol)),
+        }
+    }
+}
+
+/// Split the building into zones at each sky lobby.
+fn zones(bottom_floor: i32, top_floor: i32, sky_lobbies: &[i32]) -> Vec<Zone> {
+    let mut lobbies: Vec<i32> = sky_lobbies
+        .iter()
+        .copied()
+        .filter(|f| bottom_floor < *f && *f < top_floor)
+        .collect();
+    lobbies.sort();
+    lobbies.dedup();
+    let mut zones = Vec::new();
+    let mut lowest = bottom_floor;
+    for lobby in lobbies {
+        zones.push(Zone {
+            lowest,
+            highest: lobby,
+        });
+        lowest = lobby;
+    }
+    zones.push(Zone {
+        lowest,
+        highest: top_floor,
+    });
+    zones
+}
+
+/// Find the itinerary with the fewest changes of lift between two floors,
+/// searching breadth-first from the zones that serve the starting floor.
+fn route(zones: &[Zone], from: i32, to: i32) -> Option<Vec<i32>> {
+    let mut visited = vec![false; zones.len()];
+    let mut queue = std::collections::VecDeque::new();
+    for (i, zone) in zones.iter().enumerate() {
+        if zone.serves(from) {
+            visited[i] = true;
+            queue.push_back((i, vec![from]));
+        }
+    }
+    while let Some((i, itinerary)) = queue.pop_front() {
+        if zones[i].serves(to) {
+            let mut itinerary = itinerary;
+            itinerary.push(to);
+            return Some(itinerary);
+        }
+        for (j, zone) in zones.iter().enumerate() {
+            if visited[j] {
+                continue;
+            }
+            if let Some(transfer) = zones[i].transfer_floor(zone, to) {
+                visited[j] = true;
+                let mut itinerary = itinerary.clone();
+                itinerary.push(transfer);
+                queue.push_back((j, itinerary));
+            }
+        }
     }
+    None
 }
 
 fn sequence(n: u32) -> Vec<u32> {
@@ -393,7 +2651,7 @@ fn sequence(n: u32) -> Vec<u32> {
 }
 
 fn wait_millis(ms: u64) {
-    thread::sleep(Duration::from_millis(ms));
+    clock::sleep(Duration::from_millis(ms));
 }
 
 fn difference(x: i32, y: i32) -> i32 {
@@ -408,18 +2666,50 @@ fn start_threads(lifts: &Vec<Arc<Lift>>) {
     for lift in lifts {
         let arc = Arc::clone(lift);
         thread::spawn(move || -> Result<(i32, Direction, bool), String> {
+            let mut last = Instant::now();
             loop {
-                if let Ok(target) = arc.next_target() {
-                    arc.move_towards(target)?;
-                } else {
-                    arc.set_direction(Direction::Stopped)?;
-                }
+                arc.step()?;
                 wait_millis(100);
+                let standby = arc.energy.standby(last.elapsed());
+                arc.update_meter(|m| m.standby += standby)?;
+                last = Instant::now();
             }
         });
     }
 }
 
+/// Pass passengers arriving at a sky lobby on to their next lift, log
+/// everyone else's completed journey, let go of anyone who has given up
+/// waiting, and park idle lifts.
+fn start_controller(building: Arc<Building>, arrivals: Receiver<Passenger>) {
+    thread::spawn(move || -> Result<(), String> {
+        loop {
+            match arrivals.recv_timeout(Duration::from_millis(250)) {
+                Ok(passenger) => building.arrive(passenger)?,
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            }
+            building.update_mode()?;
+            building.abandon()?;
+            building.release()?;
+            building.park()?;
+        }
+    });
+}
+
+/// Add people waiting at a floor to a list of landings kept in floor order,
+/// merging them with anyone already waiting there.
+fn add_landing(landings: &mut Vec<(i32, usize, Duration)>, landing: (i32, usize, Duration)) {
+    let (floor, people, wait) = landing;
+    match landings.binary_search_by_key(&floor, |(f, _, _)| *f) {
+        Ok(pos) => {
+            landings[pos].1 += people;
+            landings[pos].2 = landings[pos].2.max(wait);
+        }
+        Err(pos) => landings.insert(pos, landing),
+    }
+}
+
 fn binary_add<T: Ord>(vec: &mut Vec<T>, item: T) {
     if let Err(pos) = vec.binary_search(&item) {
         vec.insert(pos, item);
@@ -430,6 +2720,44 @@ fn binary_add<T: Ord>(vec: &mut Vec<T>, item: T) {
 mod tests {
     use crate::building::*;
 
+    /// A lift serving floors -2 to 10 with its lobby on floor 0, that only
+    /// moves when a test moves it, and the channel it hands passengers off
+    /// into.
+    fn test_lift(decks: i32) -> (Lift, Receiver<Passenger>) {
+        let (handoff, arrivals) = mpsc::channel();
+        let mode = Arc::new(RwLock::new(Mode::Balanced));
+        let lift = Lift::new(
+            0,
+            0,
+            decks,
+            EnergyModel::default(),
+            mode,
+            Arc::default(),
+            handoff,
+        )
+        .with_zone(Zone {
+            lowest: -2,
+            highest: 10,
+        });
+        (lift, arrivals)
+    }
+
+    /// A building from floor 0 to 20 with one bank of lifts, none of which
+    /// move unless a test moves them.
+    fn test_building(lifts: u32) -> (Building, Receiver<Passenger>) {
+        let specs = BankSpec::zoned(0, 20, lifts, 1, &[]);
+        Building::unstarted(0, 20, &specs, EnergyModel::default())
+    }
+
+    /// The messages of every event logged about a lift, oldest first.
+    fn events(lift: &Lift) -> Vec<String> {
+        let log = lift.log.read().unwrap();
+        log.recent(None, usize::MAX, 0)
+            .into_iter()
+            .map(|e| e.message)
+            .collect()
+    }
+
     #[test]
     fn difference_check() {
         assert_eq!(difference(10, 10), 0);
@@ -437,4 +2765,541 @@ mod tests {
         assert_eq!(difference(10, 100), 90);
         assert_eq!(difference(-2, 3), 5);
     }
+
+    #[test]
+    fn double_deck_stops() {
+        let (lift, _arrivals) = test_lift(2);
+        let deck = |from, to| lift.deck_for(&Passenger::new(from, to));
+        assert_eq!(deck(0, 7), 1);
+        assert_eq!(deck(0, 8), 0);
+        assert_eq!(deck(7, 0), 1);
+        assert_eq!(deck(-1, 4), 1);
+        assert_eq!(lift.stop_for(7, 1), 6);
+        assert_eq!(lift.stop_for(8, 0), 8);
+        assert_eq!(lift.stop_for(0, 1), 0);
+        // Only one deck can reach the ends of the zone.
+        assert_eq!(deck(0, -2), 0);
+        assert_eq!(deck(5, -2), 0);
+        assert_eq!(deck(0, 10), 1);
+        assert_eq!(lift.stop_for(-2, 1), -2);
+        assert_eq!(lift.stop_for(10, 0), 9);
+        lift.press(10).unwrap();
+        assert_eq!(lift.targets().unwrap(), [9]);
+        lift.unpress(10).unwrap();
+    }
+
+    #[test]
+    fn groups_take_room() {
+        let (lift, _arrivals) = test_lift(1);
+        let (party, rest) = Passenger::new(0, 5).with_group(20).split(10);
+        assert_eq!((party.group, rest.group), (10, 10));
+        lift.add_passenger(party).unwrap();
+        assert_eq!(lift.room().unwrap(), 3);
+        lift.add_passenger(rest).unwrap();
+        assert_eq!(lift.room().unwrap(), 0);
+    }
+
+    #[test]
+    fn stops_follow_waiting_passengers() {
+        let (lift, _arrivals) = test_lift(1);
+        lift.add_passenger(Passenger::new(5, 8)).unwrap();
+        lift.add_passenger(Passenger::new(5, 2)).unwrap();
+        lift.add_passenger(Passenger::new(3, 9)).unwrap();
+        assert_eq!(lift.targets().unwrap(), [3, 5]);
+        // One of two people waiting at 5 leaves: the other still needs it.
+        lift.take_waiting(|p| p.to_floor == 8).unwrap();
+        assert_eq!(lift.targets().unwrap(), [3, 5]);
+        lift.take_waiting(|p| p.to_floor == 2).unwrap();
+        assert_eq!(lift.targets().unwrap(), [3]);
+        lift.board(3).unwrap();
+        assert_eq!(lift.targets().unwrap(), [9]);
+        // Riders cannot be taken off.
+        assert!(lift.take_waiting(|_| true).unwrap().is_empty());
+        assert_eq!(lift.targets().unwrap(), [9]);
+    }
+
+    #[test]
+    fn cancelled_car_calls_keep_needed_stops() {
+        let (lift, _arrivals) = test_lift(1);
+        lift.add_passenger(Passenger::new(0, 6)).unwrap();
+        lift.board(0).unwrap();
+        lift.press(6).unwrap();
+        lift.press(9).unwrap();
+        assert_eq!(lift.targets().unwrap(), [6, 9]);
+        lift.unpress(6).unwrap();
+        assert_eq!(lift.targets().unwrap(), [6, 9]);
+        lift.unpress(9).unwrap();
+        assert_eq!(lift.targets().unwrap(), [6]);
+        assert!(lift.unpress(9).is_err());
+    }
+
+    #[test]
+    fn no_phantom_stops() {
+        let (lift, _arrivals) = test_lift(1);
+        lift.press(3).unwrap();
+        lift.unpress(3).unwrap();
+        lift.add_passenger(Passenger::new(4, 0)).unwrap();
+        lift.take_waiting(|_| true).unwrap();
+        assert!(lift.next_target().is_err());
+        for floor in 1..=5 {
+            let (_, _, doors_open) = lift.reach_floor(floor).unwrap();
+            assert!(!doors_open);
+        }
+        assert!(events(&lift).is_empty());
+        // A floor someone does want is still a stop.
+        lift.press(6).unwrap();
+        assert_eq!(lift.targets().unwrap(), [6]);
+    }
+
+    #[test]
+    fn idle_lifts_park() {
+        let (building, _arrivals) = test_building(1);
+        let lift = &building.banks[0].lifts[0];
+        lift.set_idle(Idle::Busy).unwrap();
+        assert_eq!(lift.parking_target().unwrap(), None);
+        assert!(matches!(lift.idle().unwrap(), Idle::Since(_)));
+        lift.park(25).unwrap();
+        assert_eq!(lift.idle().unwrap(), Idle::Parking(20));
+        assert_eq!(lift.parking_target().unwrap(), Some(20));
+        lift.set_floor(20).unwrap();
+        assert_eq!(lift.parking_target().unwrap(), None);
+        assert_eq!(lift.idle().unwrap(), Idle::Parked);
+        // A new policy lets parked lifts be parked again.
+        building.set_parking(Parking::Lobby).unwrap();
+        assert!(matches!(lift.idle().unwrap(), Idle::Since(_)));
+        lift.park(20).unwrap();
+        assert_eq!(lift.idle().unwrap(), Idle::Parked);
+    }
+
+    #[test]
+    fn modes_follow_traffic() {
+        let (building, _arrivals) = test_building(1);
+        let lift = &building.banks[0].lifts[0];
+        assert_eq!(building.mode().unwrap(), (Mode::Balanced, false));
+        lift.park(10).unwrap();
+        building.set_mode(Some(Mode::DownPeak)).unwrap();
+        assert_eq!(building.mode().unwrap(), (Mode::DownPeak, false));
+        assert!(matches!(lift.idle().unwrap(), Idle::Since(_)));
+        // A fixed mode ignores the traffic.
+        for floor in 1..=10 {
+            building.calls.write().unwrap().record(0, floor);
+        }
+        building.update_mode().unwrap();
+        assert_eq!(building.mode().unwrap(), (Mode::DownPeak, false));
+        building.set_mode(None).unwrap();
+        assert_eq!(building.mode().unwrap(), (Mode::UpPeak, true));
+        for floor in 1..=20 {
+            building.calls.write().unwrap().record(floor, 0);
+        }
+        building.update_mode().unwrap();
+        assert_eq!(building.mode().unwrap(), (Mode::DownPeak, true));
+    }
+
+    #[test]
+    fn down_peak_sweeps_to_the_lobby() {
+        let (building, _arrivals) = test_building(1);
+        let lift = &building.banks[0].lifts[0];
+        building.set_mode(Some(Mode::DownPeak)).unwrap();
+        lift.park(15).unwrap();
+        assert_eq!(lift.parking_target().unwrap(), Some(15));
+        lift.set_floor(15).unwrap();
+        assert_eq!(lift.parking_target().unwrap(), Some(0));
+        assert_eq!(lift.idle().unwrap(), Idle::Sweeping);
+        lift.set_floor(7).unwrap();
+        assert_eq!(lift.parking_target().unwrap(), Some(0));
+        lift.set_floor(0).unwrap();
+        assert_eq!(lift.parking_target().unwrap(), None);
+        assert!(matches!(lift.idle().unwrap(), Idle::Since(_)));
+        // A lift already on its upper floor sets off down straight away.
+        lift.set_floor(12).unwrap();
+        lift.park(12).unwrap();
+        assert_eq!(lift.idle().unwrap(), Idle::Sweeping);
+        // Anyone it is given on the way down ends the sweep.
+        building.respond(Passenger::new(9, 0)).unwrap();
+        assert_eq!(lift.idle().unwrap(), Idle::Busy);
+    }
+
+    #[test]
+    fn detail_looks_ahead() {
+        let (lift, _arrivals) = test_lift(1);
+        lift.press(2).unwrap();
+        lift.add_passenger(Passenger::new(6, 1).with_group(3))
+            .unwrap();
+        let detail = lift.detail("Main").unwrap();
+        assert_eq!(detail.targets, vec![2, 6]);
+        assert_eq!(detail.next_target, Some(2));
+        assert!(detail.riding.is_empty());
+        assert_eq!(detail.assigned[0].to_string(), "6 → 1 x3");
+        // Nothing below, so a lift going down turns round for the nearest.
+        lift.set_direction(Direction::Down).unwrap();
+        assert_eq!(lift.detail("Main").unwrap().next_target, Some(2));
+    }
+
+    #[test]
+    fn stats_count_groups() {
+        let (building, _arrivals) = test_building(1);
+        building
+            .respond(Passenger::new(15, 0).with_group(4))
+            .unwrap();
+        let stats = building.stats(10).unwrap();
+        assert_eq!((stats.waiting, stats.riding, stats.throughput), (4, 0, 0));
+        let capacity = EnergyModel::default().capacity() as f64;
+        assert_eq!(stats.utilisation, vec![(0, 4.0 / capacity)]);
+        assert!(stats.waits.is_empty());
+    }
+
+    #[test]
+    fn reassigned_calls_leave_no_stops() {
+        let (building, _arrivals) = test_building(2);
+        let stops = |id: u32| building.lift(id).unwrap().targets().unwrap();
+        building.respond(Passenger::new(10, 2)).unwrap();
+        building.cancel_call(10, 2).unwrap();
+        assert_eq!((stops(0), stops(1)), (vec![], vec![]));
+        let assigned = building.respond(Passenger::new(12, 2)).unwrap().unwrap();
+        let boarded = building.wrong_car(12, 2).unwrap();
+        assert_ne!(assigned, boarded);
+        assert_eq!((stops(assigned), stops(boarded)), (vec![], vec![12]));
+        building.fail(boarded, Fault::OutOfService).unwrap();
+        assert_eq!((stops(assigned), stops(boarded)), (vec![12], vec![]));
+        // With the other lift out of service there is no wrong car to take.
+        assert!(building.wrong_car(12, 2).is_err());
+        assert_eq!((stops(assigned), stops(boarded)), (vec![12], vec![]));
+    }
+
+    #[test]
+    fn stranded_by_a_fault_give_up() {
+        let (building, _arrivals) = test_building(1);
+        building
+            .respond(Passenger::new(5, 10).with_group(3))
+            .unwrap();
+        building.fail(0, Fault::OutOfService).unwrap();
+        assert_eq!(building.abandonment().unwrap().0, 3);
+        let events = building.events(None, 1, 0).unwrap();
+        assert!(events[0].message.contains("gave up at 5"));
+        assert!(building.banks[0].lifts[0].targets().unwrap().is_empty());
+    }
+
+    #[test]
+    fn impatient_callers_give_up() {
+        let (building, _arrivals) = test_building(1);
+        let patience = |p| Behaviour {
+            patience: Some(p),
+            ..Behaviour::default()
+        };
+        building.set_behaviour(patience(Duration::ZERO)).unwrap();
+        building
+            .respond(Passenger::new(5, 10).with_group(2))
+            .unwrap();
+        building
+            .set_behaviour(patience(Duration::from_secs(3600)))
+            .unwrap();
+        building.respond(Passenger::new(6, 9)).unwrap();
+        clock::sleep(Duration::from_millis(1));
+        building.abandon().unwrap();
+        assert_eq!(building.abandonment().unwrap().0, 2);
+        assert_eq!(building.banks[0].lifts[0].targets().unwrap(), [6]);
+    }
+
+    #[test]
+    fn short_trips_may_be_walked() {
+        let (building, _arrivals) = test_building(1);
+        let stairs = |share| Behaviour {
+            stairs_floors: 2,
+            stairs_share: share,
+            ..Behaviour::default()
+        };
+        building.set_behaviour(stairs(1.0)).unwrap();
+        assert_eq!(building.respond(Passenger::new(5, 7)).unwrap(), None);
+        assert_eq!(building.respond(Passenger::new(5, 8)).unwrap(), Some(0));
+        // Someone already where they are going has no trip to make.
+        assert!(building.respond(Passenger::new(5, 5)).is_err());
+        building.set_behaviour(stairs(0.0)).unwrap();
+        assert_eq!(building.respond(Passenger::new(7, 5)).unwrap(), Some(0));
+        assert_eq!(building.abandonment().unwrap().1, 1);
+    }
+
+    #[test]
+    fn energy_dispatch_prefers_heavy_cars_going_down() {
+        let (building, _arrivals) = test_building(2);
+        let (loaded, empty) = (building.lift(0).unwrap(), building.lift(1).unwrap());
+        loaded
+            .add_passenger(Passenger {
+                riding: true,
+                ..Passenger::new(20, 0).with_group(10)
+            })
+            .unwrap();
+        loaded.set_floor(12).unwrap();
+        empty.set_floor(10).unwrap();
+        // The counterweight has to be hauled up to lower a light car, but a
+        // heavy one falls under its own weight.
+        let passenger = Passenger::new(10, 0);
+        let cost = |lift: &Lift| lift.energy_cost(&passenger).unwrap();
+        assert!(cost(loaded) < cost(empty));
+        assert_eq!(building.respond(passenger.clone()).unwrap(), Some(1));
+        building.cancel_call(10, 0).unwrap();
+        building.set_dispatch(Dispatch::Energy).unwrap();
+        assert_eq!(building.respond(passenger).unwrap(), Some(0));
+    }
+
+    #[test]
+    fn etd_spares_busy_cars() {
+        let (building, _arrivals) = test_building(2);
+        building.set_dispatch(Dispatch::Etd).unwrap();
+        let busy = building.respond(Passenger::new(2, 3)).unwrap().unwrap();
+        let idle = 1 - busy;
+        building.lift(idle).unwrap().set_floor(4).unwrap();
+        // Waiting for the idle car takes longer than riding with the busy
+        // one, but holding up the people in it would cost more still.
+        let cost =
+            |id: u32, p: &Passenger| building.lift(id).unwrap().time_to_destination(p).unwrap();
+        let passenger = Passenger::new(0, 6).with_group(3);
+        assert!(cost(busy, &passenger) > cost(idle, &passenger));
+        assert_eq!(building.respond(passenger).unwrap(), Some(idle));
+    }
+
+    #[test]
+    fn groups_skip_full_cars() {
+        let (building, _arrivals) = test_building(2);
+        let full = &building.banks[0].lifts[0];
+        full.add_passenger(Passenger {
+            riding: true,
+            ..Passenger::new(0, 5).with_group(13)
+        })
+        .unwrap();
+        building.lift(1).unwrap().set_floor(10).unwrap();
+        let group = Passenger::new(1, 8).with_group(5);
+        assert_eq!(building.respond(group).unwrap(), Some(1));
+        assert_eq!(full.targets().unwrap(), [5]);
+        assert_eq!(building.lift(1).unwrap().room().unwrap(), 8);
+    }
+
+    #[test]
+    fn priority_calls_get_a_car_to_themselves() {
+        let (building, _arrivals) = test_building(2);
+        let busy = building.respond(Passenger::new(1, 3)).unwrap().unwrap();
+        let empty = 1 - busy;
+        building.lift(empty).unwrap().set_floor(10).unwrap();
+        // The empty car is sent even though the busy one is nearer.
+        let vip = Passenger::new(2, 9).with_priority(Priority::Vip);
+        assert_eq!(building.respond(vip).unwrap(), Some(empty));
+        // Then no one else shares it, however near it is.
+        assert_eq!(building.respond(Passenger::new(9, 12)).unwrap(), Some(busy));
+    }
+
+    #[test]
+    fn priority_calls_wait_for_an_empty_car() {
+        let (building, _arrivals) = test_building(2);
+        for id in 0..2 {
+            building
+                .lift(id)
+                .unwrap()
+                .add_passenger(Passenger::new(4 + id as i32, 9))
+                .unwrap();
+        }
+        let vip = Passenger::new(2, 9).with_priority(Priority::Vip);
+        assert_eq!(building.respond(vip).unwrap(), None);
+        let events = building.events(None, 1, 0).unwrap();
+        assert!(events[0].message.contains("waiting for an empty car"));
+        assert_eq!(building.stats(0).unwrap().waiting, 3);
+        building.release().unwrap();
+        assert_eq!(building.held.read().unwrap().len(), 1);
+        // Once a car is free, the held passenger has it to themselves.
+        building.cancel_call(5, 9).unwrap();
+        building.release().unwrap();
+        assert!(building.held.read().unwrap().is_empty());
+        assert_eq!(building.lift(1).unwrap().targets().unwrap(), [2]);
+    }
+
+    #[test]
+    fn emergency_calls_take_the_nearest_car() {
+        let (building, _arrivals) = test_building(2);
+        let busy = building.respond(Passenger::new(1, 3)).unwrap().unwrap();
+        building.lift(1 - busy).unwrap().set_floor(10).unwrap();
+        building.set_dispatch(Dispatch::Energy).unwrap();
+        let emergency = Passenger::new(2, 9).with_priority(Priority::Emergency);
+        assert_eq!(building.respond(emergency).unwrap(), Some(busy));
+    }
+
+    #[test]
+    fn independent_service_hands_over_calls() {
+        let (building, _arrivals) = test_building(2);
+        let first = building.respond(Passenger::new(5, 10)).unwrap().unwrap();
+        building.set_independent(first, true).unwrap();
+        assert!(building.independent(first).unwrap());
+        let lift = building.lift(first).unwrap();
+        assert!(lift.targets().unwrap().is_empty());
+        assert!(lift.get_info().unwrap().2);
+        // Only the other lift takes calls now, and once it goes too there
+        // is no one left to take them.
+        let other = building.respond(Passenger::new(3, 8)).unwrap().unwrap();
+        assert_ne!(other, first);
+        building.set_independent(other, true).unwrap();
+        assert_eq!(building.abandonment().unwrap().0, 2);
+        building.set_independent(first, false).unwrap();
+        assert!(!building.independent(first).unwrap());
+        assert!(!lift.get_info().unwrap().2);
+    }
+
+    #[test]
+    fn independent_lifts_hold_their_doors() {
+        let (building, _arrivals) = test_building(1);
+        let lift = building.lift(0).unwrap();
+        building.set_independent(0, true).unwrap();
+        building.car_call(0, 2).unwrap();
+        building.car_call(0, 4).unwrap();
+        assert!(!lift.get_info().unwrap().2);
+        assert_eq!(lift.next_move().unwrap(), Some(2));
+        lift.reach_floor(1).unwrap();
+        lift.reach_floor(2).unwrap();
+        // The car waits at its first stop, however often it is stepped.
+        for _ in 0..3 {
+            lift.step().unwrap();
+            assert_eq!(lift.get_info().unwrap(), (2, Direction::Stopped, true));
+        }
+        assert_eq!(lift.targets().unwrap(), [4]);
+        // Until it is given another command.
+        building.car_call(0, 6).unwrap();
+        assert!(!lift.get_info().unwrap().2);
+        assert_eq!(lift.next_move().unwrap(), Some(4));
+    }
+
+    #[test]
+    fn recall_lets_riders_out() {
+        let (building, arrivals) = test_building(1);
+        let lift = &building.banks[0].lifts[0];
+        building.respond(Passenger::new(5, 2)).unwrap();
+        lift.add_passenger(Passenger {
+            riding: true,
+            ..Passenger::new(12, 15)
+        })
+        .unwrap();
+        building.recall(None).unwrap();
+        assert_eq!(building.abandonment().unwrap().0, 1);
+        lift.return_to(0).unwrap();
+        let rider = arrivals.try_recv().unwrap();
+        assert_eq!((rider.to_floor, rider.itinerary.clone()), (0, vec![12, 0]));
+        assert!(arrivals.try_recv().is_err());
+        building.arrive(rider).unwrap();
+        assert_eq!(building.journey_summary().unwrap().0, 1);
+        assert!(!lift.has_passengers().unwrap());
+    }
+
+    #[test]
+    fn stranded_transfers_give_up() {
+        let specs = BankSpec::zoned(0, 40, 2, 1, &[20]);
+        let (building, _arrivals) = Building::unstarted(0, 40, &specs, EnergyModel::default());
+        building.recall(None).unwrap();
+        let passenger = Passenger::with_itinerary(vec![5, 20, 30]).with_group(2);
+        building.arrive(passenger).unwrap();
+        assert_eq!(building.abandonment().unwrap(), (2, 0, 1.0));
+        let events = building.events(None, 1, 0).unwrap();
+        assert!(events[0].message.contains("gave up at 20"));
+    }
+
+    #[test]
+    fn transfers_are_not_new_calls() {
+        let specs = BankSpec::zoned(0, 40, 2, 1, &[20]);
+        let (building, _arrivals) = Building::unstarted(0, 40, &specs, EnergyModel::default());
+        let passenger = Passenger::with_itinerary(vec![0, 20, 30]);
+        building.respond(passenger.clone()).unwrap();
+        building.arrive(passenger).unwrap();
+        let traffic = building.traffic().unwrap();
+        assert_eq!(
+            (traffic.incoming, traffic.outgoing, traffic.interfloor),
+            (1, 0, 0)
+        );
+    }
+
+    #[test]
+    fn landings_add_up_across_banks() {
+        let specs = BankSpec::zoned(0, 40, 2, 1, &[20]);
+        let (building, _arrivals) = Building::unstarted(0, 40, &specs, EnergyModel::default());
+        building.respond(Passenger::new(20, 30)).unwrap();
+        building
+            .respond(Passenger::new(20, 5).with_group(2))
+            .unwrap();
+        building.respond(Passenger::new(3, 0)).unwrap();
+        let people = |landings: Vec<(i32, usize, Duration)>| {
+            landings
+                .into_iter()
+                .map(|(floor, people, _)| (floor, people))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            people(building.banks[0].landings().unwrap()),
+            [(3, 1), (20, 2)]
+        );
+        assert_eq!(people(building.banks[1].landings().unwrap()), [(20, 1)]);
+        assert_eq!(people(building.landings().unwrap()), [(3, 1), (20, 3)]);
+    }
+
+    #[test]
+    fn zones_split_at_sky_lobbies() {
+        let zones = zones(-2, 60, &[40, 20, 60, 20]);
+        assert_eq!(
+            zones,
+            vec![
+                Zone {
+                    lowest: -2,
+                    highest: 20
+                },
+                Zone {
+                    lowest: 20,
+                    highest: 40
+                },
+                Zone {
+                    lowest: 40,
+                    highest: 60
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn bank_specs() {
+        assert_eq!(
+            "Goods:1:-2:10".parse(),
+            Ok(BankSpec {
+                name: "Goods".to_string(),
+                lifts: 1,
+                decks: 1,
+                zone: Some(Zone {
+                    lowest: -2,
+                    highest: 10
+                }),
+                lobby: None,
+            })
+        );
+        assert_eq!(
+            "East:3".parse::<BankSpec>().map(|b| (b.lifts, b.zone)),
+            Ok((3, None))
+        );
+        assert_eq!(
+            "Express:4x2:0:30"
+                .parse::<BankSpec>()
+                .map(|b| (b.lifts, b.decks)),
+            Ok((4, 2))
+        );
+        assert!("Express:4x3".parse::<BankSpec>().is_err());
+        assert!("East:3:10".parse::<BankSpec>().is_err());
+        assert!("East:3:10:0".parse::<BankSpec>().is_err());
+        let check = |s: &str| s.parse::<BankSpec>().unwrap().check(0, 15);
+        assert!(check("Goods:1:0:15:0").is_ok());
+        assert!(check("Goods:1:-5:100").is_err());
+        assert!(check("Goods:1:0:10:12").is_err());
+        let lifts: Vec<u32> = BankSpec::zoned(0, 60, 5, 1, &[20, 40])
+            .iter()
+            .map(|b| b.lifts)
+            .collect();
+        assert_eq!(lifts, vec![2, 2, 1]);
+    }
+
+    #[test]
+    fn route_through_sky_lobbies() {
+        let zones = zones(0, 60, &[20, 40]);
+        assert_eq!(route(&zones, 0, 60), Some(vec![0, 20, 40, 60]));
+        assert_eq!(route(&zones, 55, 25), Some(vec![55, 40, 25]));
+        assert_eq!(route(&zones, 3, 17), Some(vec![3, 17]));
+        assert_eq!(route(&zones, 0, 61), None);
+    }
 }
@@ -0,0 +1,226 @@
+use std::{
+    ops::Sub,
+    sync::{Condvar, LazyLock, Mutex},
+    time::Duration,
+};
+
+/// The speeds the simulation can run at, as multiples of real time, from
+/// slowest to fastest.
+const SPEEDS: [f64; 9] = [0.25, 0.5, 1.0, 2.0, 5.0, 10.0, 20.0, 50.0, 100.0];
+
+/// How far a single step moves the simulation on: the time a lift takes to
+/// travel one floor.
+const STEP: Duration = Duration::from_millis(500);
+
+/// The longest a sleeper goes without checking whether the speed has
+/// changed.
+const TICK: Duration = Duration::from_millis(50);
+
+/// The clock every lift, passenger and scenario keeps time by.
+static CLOCK: LazyLock<Clock> = LazyLock::new(Clock::new);
+
+/// A point in simulated time, used in place of `std::time::Instant` so that
+/// everything speeds up, slows down and pauses together.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Instant(Duration);
+
+impl Instant {
+    pub fn now() -> Instant {
+        CLOCK.now()
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        Instant::now() - *self
+    }
+
+    /// The simulated time from the start of the run to this instant.
+    pub fn since_start(&self) -> Duration {
+        self.0
+    }
+}
+
+impl Sub for Instant {
+    type Output = Duration;
+
+    fn sub(self, earlier: Instant) -> Duration {
+        self.0.saturating_sub(earlier.0)
+    }
+}
+
+/// Block the calling thread for a span of simulated time.
+pub fn sleep(duration: Duration) {
+    CLOCK.sleep(duration)
+}
+
+pub fn speed() -> f64 {
+    CLOCK.speed()
+}
+
+pub fn set_speed(speed: f64) {
+    CLOCK.set_speed(speed)
+}
+
+pub fn paused() -> bool {
+    CLOCK.paused()
+}
+
+pub fn set_paused(paused: bool) {
+    CLOCK.set_paused(paused)
+}
+
+/// Pause the simulation and move it on by one step.
+pub fn step() {
+    CLOCK.set_paused(true);
+    CLOCK.advance(STEP)
+}
+
+/// The next speed up or down from `speed` in `SPEEDS`, staying at the ends.
+pub fn next_speed(speed: f64, faster: bool) -> f64 {
+    if faster {
+        SPEEDS.into_iter().find(|s| *s > speed).unwrap_or(speed)
+    } else {
+        SPEEDS
+            .into_iter()
+            .rev()
+            .find(|s| *s < speed)
+            .unwrap_or(speed)
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    /// The simulated time at `since`.
+    time: Duration,
+    since: std::time::Instant,
+    speed: f64,
+    paused: bool,
+}
+
+#[derive(Debug)]
+struct Clock {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+impl State {
+    fn now(&self) -> Duration {
+        if self.paused {
+            self.time
+        } else {
+            self.time + self.since.elapsed().mul_f64(self.speed)
+        }
+    }
+
+    /// Start counting from the current time again, before the speed or
+    /// pause changes.
+    fn rebase(&mut self) {
+        self.time = self.now();
+        self.since = std::time::Instant::now();
+    }
+}
+
+impl Clock {
+    fn new() -> Clock {
+        Clock {
+            state: Mutex::new(State {
+                time: Duration::ZERO,
+                since: std::time::Instant::now(),
+                speed: 1.0,
+                paused: false,
+            }),
+            changed: Condvar::new(),
+        }
+    }
+
+    // A poisoned lock only means another thread panicked while holding it;
+    // the state itself is always consistent, so carry on with it.
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn now(&self) -> Instant {
+        Instant(self.state().now())
+    }
+
+    fn sleep(&self, duration: Duration) {
+        let mut state = self.state();
+        let until = state.now() + duration;
+        loop {
+            let now = state.now();
+            if now >= until {
+                return;
+            }
+            let wait = if state.paused {
+                TICK
+            } else {
+                (until - now).div_f64(state.speed).min(TICK)
+            };
+            state = match self.changed.wait_timeout(state, wait) {
+                Ok((state, _)) => state,
+                Err(e) => e.into_inner().0,
+            };
+        }
+    }
+
+    fn speed(&self) -> f64 {
+        self.state().speed
+    }
+
+    fn set_speed(&self, speed: f64) {
+        let mut state = self.state();
+        state.rebase();
+        state.speed = speed;
+        self.changed.notify_all();
+    }
+
+    fn paused(&self) -> bool {
+        self.state().paused
+    }
+
+    fn set_paused(&self, paused: bool) {
+        let mut state = self.state();
+        state.rebase();
+        state.paused = paused;
+        self.changed.notify_all();
+    }
+
+    fn advance(&self, duration: Duration) {
+        let mut state = self.state();
+        state.rebase();
+        state.time += duration;
+        self.changed.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clock::*;
+    use std::{sync::Arc, thread};
+
+    #[test]
+    fn steps_while_paused() {
+        let clock = Arc::new(Clock::new());
+        clock.set_paused(true);
+        let start = clock.now();
+        let sleeper = {
+            let clock = Arc::clone(&clock);
+            thread::spawn(move || clock.sleep(Duration::from_millis(800)))
+        };
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(clock.now(), start);
+        clock.advance(STEP);
+        thread::sleep(Duration::from_millis(100));
+        assert!(!sleeper.is_finished());
+        clock.advance(STEP);
+        sleeper.join().unwrap();
+        assert_eq!(clock.now() - start, 2 * STEP);
+    }
+
+    #[test]
+    fn speeds_stop_at_the_ends() {
+        assert_eq!(next_speed(1.0, true), 2.0);
+        assert_eq!(next_speed(1.0, false), 0.5);
+        assert_eq!(next_speed(100.0, true), 100.0);
+        assert_eq!(next_speed(0.25, false), 0.25);
+    }
+}
@@ -0,0 +1,70 @@
+use crate::keys::KeyMap;
+use std::{fs, str::FromStr};
+
+/// Settings read from a config file. The file is made of sections, each
+/// started by a `[name]` line. The only section so far is `[keys]`, whose
+/// lines rebind actions as in `quit = x esc`. Blank lines and lines starting
+/// with `#` are ignored.
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    pub keys: KeyMap,
+}
+
+impl FromStr for Config {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut config = Config::default();
+        let mut section = None;
+        for (number, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |e: String| format!("Line {}: {}", number + 1, e);
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                match name.trim() {
+                    "keys" => section = Some("keys"),
+                    name => return Err(error(format!("Unknown section '{}'", name))),
+                }
+                continue;
+            }
+            let (name, value) = line
+                .split_once('=')
+                .ok_or(error("Expected a name, '=' and a value".to_string()))?;
+            match section {
+                Some("keys") => config.keys.bind(name.trim(), value).map_err(error)?,
+                _ => return Err(error("Expected a section first".to_string())),
+            }
+        }
+        Ok(config)
+    }
+}
+
+impl Config {
+    pub fn load(path: &str) -> Result<Config, String> {
+        fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path, e))?
+            .parse()
+            .map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::*;
+    use crate::keys::Action;
+    use crossterm::event::KeyCode;
+
+    #[test]
+    fn parse_config() {
+        let config: Config = "# Dvorak\n[keys]\nquit = ' esc\nview = k\n"
+            .parse()
+            .unwrap();
+        assert_eq!(config.keys.action(KeyCode::Char('\'')), Some(Action::Quit));
+        assert_eq!(config.keys.action(KeyCode::Char('k')), Some(Action::View));
+        assert!("quit = x".parse::<Config>().is_err());
+        assert!("[colours]\n".parse::<Config>().is_err());
+        assert!("[keys]\nquit x\n".parse::<Config>().is_err());
+    }
+}
@@ -0,0 +1,113 @@
+use crate::building::Stats;
+use ratatui::{
+    buffer::Buffer,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Gauge, Paragraph, Sparkline, Widget},
+};
+
+/// Lines of figures above the utilisation gauges.
+const FIGURE_LINES: u16 = 4;
+
+/// Height of the sparkline of recent waits, including its title.
+const SPARKLINE_HEIGHT: u16 = 4;
+
+/// Live statistics for watching congestion build: who is waiting and
+/// riding, how long they have waited, recent throughput and waits, and how
+/// full each lift is.
+#[derive(Debug)]
+pub struct Dashboard {
+    stats: Stats,
+}
+
+impl Dashboard {
+    pub fn new(stats: Stats) -> Dashboard {
+        Dashboard { stats }
+    }
+
+    /// The height needed to show everything.
+    pub fn height(&self) -> u16 {
+        FIGURE_LINES + SPARKLINE_HEIGHT + self.stats.utilisation.len() as u16
+    }
+
+    fn figures(&self) -> Vec<Line<'static>> {
+        let stats = &self.stats;
+        let field = |name: &str, value: String| {
+            Line::from(vec![
+                Span::from(format!("{}: ", name)).bold(),
+                Span::from(value),
+            ])
+        };
+        let wait_style = match stats.longest_wait.as_secs() {
+            0..=29 => Style::new(),
+            30..=59 => Style::new().yellow(),
+            _ => Style::new().red(),
+        };
+        vec![
+            field(
+                "People",
+                format!("{} waiting, {} riding", stats.waiting, stats.riding),
+            ),
+            Line::from(vec![
+                Span::from("Wait: ").bold(),
+                Span::styled(
+                    format!(
+                        "{:.1}s average, {:.1}s longest",
+                        stats.average_wait.as_secs_f64(),
+                        stats.longest_wait.as_secs_f64()
+                    ),
+                    wait_style,
+                ),
+            ]),
+            field("Throughput", format!("{} per 5 minutes", stats.throughput)),
+            Line::from(Span::from("Utilisation").bold()),
+        ]
+    }
+}
+
+impl Widget for Dashboard {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let mut constraints = vec![Constraint::Length(FIGURE_LINES)];
+        constraints.extend(self.stats.utilisation.iter().map(|_| Constraint::Length(1)));
+        constraints.push(Constraint::Length(SPARKLINE_HEIGHT));
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(constraints)
+            .split(area);
+        Paragraph::new(self.figures()).render(rows[0], buf);
+        for ((id, share), row) in self.stats.utilisation.iter().zip(rows[1..].iter()) {
+            let style = if *share >= 1.0 {
+                Style::new().red().on_black()
+            } else {
+                Style::new().green().on_black()
+            };
+            Gauge::default()
+                .gauge_style(style)
+                .ratio(*share)
+                .label(format!("Lift {}: {:.0}%", id, share * 100.0))
+                .render(*row, buf);
+        }
+        let longest = self.stats.waits.iter().max().copied().unwrap_or_default();
+        // Tenths of a second, so that short waits still show.
+        let waits: Vec<u64> = self
+            .stats
+            .waits
+            .iter()
+            .map(|w| (w.as_millis() / 100) as u64)
+            .collect();
+        Sparkline::default()
+            .block(
+                Block::default().title(
+                    Span::from(format!(
+                        "Recent waits (up to {:.1}s)",
+                        longest.as_secs_f64()
+                    ))
+                    .bold(),
+                ),
+            )
+            .style(Style::new().yellow())
+            .data(&waits)
+            .render(rows[rows.len() - 1], buf);
+    }
+}
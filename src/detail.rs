@@ -0,0 +1,94 @@
+use crate::building::{Detail, Direction, Passenger};
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Style, Stylize},
+    text::{Line, Span},
+    widgets::{Paragraph, Widget, Wrap},
+};
+
+/// A side panel with the full state of the selected lift.
+#[derive(Debug)]
+pub struct DetailView {
+    detail: Detail,
+}
+
+impl DetailView {
+    pub fn new(detail: Detail) -> DetailView {
+        DetailView { detail }
+    }
+
+    fn lines(&self) -> Vec<Line<'static>> {
+        let detail = &self.detail;
+        let car = &detail.car;
+        let field = |name: &str, value: String, style: Style| {
+            Line::from(vec![
+                Span::from(format!("{}: ", name)).bold(),
+                Span::styled(value, style),
+            ])
+        };
+        let floor = if car.decks == 2 {
+            format!("{}/{}", car.floor, car.floor + 1)
+        } else {
+            car.floor.to_string()
+        };
+        let direction = match detail.direction {
+            Direction::Up => "going up",
+            Direction::Down => "going down",
+            Direction::Stopped => "stopped",
+        };
+        let doors = if car.doors_open { "open" } else { "closed" };
+        let mut lines = vec![
+            Line::from(Span::from(format!("Lift {} ({})", car.id, detail.bank)).bold()),
+            field("Floor", format!("{}, {}", floor, direction), Style::new()),
+            field("Doors", doors.to_string(), Style::new()),
+        ];
+        if let Some(fault) = car.fault {
+            lines.push(field("Fault", fault.to_string(), Style::new().red()));
+        }
+        if let Some(service) = &detail.service {
+            lines.push(field("Service", service.clone(), Style::new().yellow()));
+        }
+        let next = detail
+            .next_target
+            .map_or("none".to_string(), |t| t.to_string());
+        lines.push(field("Next target", next, Style::new()));
+        let targets = detail
+            .targets
+            .iter()
+            .map(|t| t.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        lines.push(field("Targets", targets, Style::new()));
+        lines.push(field("Trips", detail.trips.to_string(), Style::new()));
+        lines.push(field(
+            "Distance",
+            format!("{} floors", detail.floors),
+            Style::new(),
+        ));
+        passenger_lines(&mut lines, "Riding", &detail.riding);
+        passenger_lines(&mut lines, "Assigned", &detail.assigned);
+        lines
+    }
+}
+
+/// A heading with the number of people, counting everyone in a group, and
+/// then each passenger.
+fn passenger_lines(lines: &mut Vec<Line<'static>>, heading: &str, passengers: &[Passenger]) {
+    let people: usize = passengers.iter().map(Passenger::group).sum();
+    lines.push(Line::from(""));
+    lines.push(Line::from(
+        Span::from(format!("{} ({})", heading, people)).bold(),
+    ));
+    for passenger in passengers {
+        lines.push(Line::from(format!("  {}", passenger)));
+    }
+}
+
+impl Widget for DetailView {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        Paragraph::new(self.lines())
+            .wrap(Wrap { trim: false })
+            .render(area, buf);
+    }
+}
@@ -0,0 +1,178 @@
+use std::time::Duration;
+
+const GRAVITY: f64 = 9.81;
+const JOULES_PER_KWH: f64 = 3_600_000.0;
+
+/// Physical parameters of a traction lift, used to estimate how much energy
+/// it draws. Energies are in joules unless stated otherwise.
+#[derive(Debug, Clone, Copy)]
+pub struct EnergyModel {
+    /// Storey height in metres.
+    pub floor_height: f64,
+    /// Mass of the empty car in kilograms.
+    pub car_mass: f64,
+    /// Rated load of the car in kilograms.
+    pub rated_load: f64,
+    /// Fraction of the rated load balanced by the counterweight.
+    pub balance: f64,
+    pub passenger_mass: f64,
+    /// Rated speed in metres per second.
+    pub speed: f64,
+    pub motor_efficiency: f64,
+    /// Force lost to friction in the shaft and machine, in newtons.
+    pub friction: f64,
+    /// Power drawn by the controller, lighting and fans at all times, in
+    /// watts.
+    pub standby_power: f64,
+    /// Whether the drive feeds energy back to the supply when the load pulls
+    /// the car, rather than burning it off in a resistor.
+    pub regenerative: bool,
+    pub regen_efficiency: f64,
+}
+
+impl Default for EnergyModel {
+    fn default() -> Self {
+        EnergyModel {
+            floor_height: 3.5,
+            car_mass: 1200.0,
+            rated_load: 1000.0,
+            balance: 0.5,
+            passenger_mass: 75.0,
+            speed: 1.6,
+            motor_efficiency: 0.8,
+            friction: 600.0,
+            standby_power: 250.0,
+            regenerative: false,
+            regen_efficiency: 0.6,
+        }
+    }
+}
+
+pub fn kwh(joules: f64) -> f64 {
+    joules / JOULES_PER_KWH
+}
+
+impl EnergyModel {
+    /// The number of passengers the car can take.
+    pub fn capacity(&self) -> usize {
+        (self.rated_load / self.passenger_mass) as usize
+    }
+
+    /// Weight of the car and its load less the counterweight, in kilograms.
+    /// A light car is pulled up by the counterweight, a heavy one falls.
+    fn net_load(&self, occupants: usize) -> f64 {
+        occupants as f64 * self.passenger_mass - self.balance * self.rated_load
+    }
+
+    fn moving_mass(&self, occupants: usize) -> f64 {
+        2.0 * self.car_mass
+            + self.balance * self.rated_load
+            + occupants as f64 * self.passenger_mass
+    }
+
+    /// Energy drawn from (or, when negative, returned to) the supply by
+    /// travelling `floors` storeys, upwards if positive.
+    pub fn travel(&self, floors: i32, occupants: usize) -> f64 {
+        let height = floors as f64 * self.floor_height;
+        let work = self.net_load(occupants) * GRAVITY * height + self.friction * height.abs();
+        self.supply(work)
+    }
+
+    /// Energy needed to accelerate from rest to rated speed and brake back
+    /// to a stop.
+    pub fn start(&self, occupants: usize) -> f64 {
+        let kinetic = 0.5 * self.moving_mass(occupants) * self.speed * self.speed;
+        self.supply(kinetic) + self.supply(-kinetic)
+    }
+
+    pub fn standby(&self, elapsed: Duration) -> f64 {
+        self.standby_power * elapsed.as_secs_f64()
+    }
+
+    fn supply(&self, work: f64) -> f64 {
+        if work >= 0.0 {
+            work / self.motor_efficiency
+        } else if self.regenerative {
+            work * self.regen_efficiency
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Running totals of the energy used by one lift.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Meter {
+    pub motor: f64,
+    pub regenerated: f64,
+    pub standby: f64,
+    pub trips: u32,
+    pub floors: u32,
+    moving: bool,
+}
+
+impl Meter {
+    pub fn record(&mut self, joules: f64) {
+        if joules >= 0.0 {
+            self.motor += joules;
+        } else {
+            self.regenerated -= joules;
+        }
+    }
+
+    /// Record a move of one floor, counting a new trip and paying for the
+    /// acceleration if the car was at rest.
+    pub fn travel(&mut self, model: &EnergyModel, floors: i32, occupants: usize) {
+        if !self.moving {
+            self.moving = true;
+            self.trips += 1;
+            self.record(model.start(occupants));
+        }
+        self.floors += floors.unsigned_abs();
+        self.record(model.travel(floors, occupants));
+    }
+
+    pub fn stop(&mut self) {
+        self.moving = false;
+    }
+
+    pub fn kwh(&self) -> f64 {
+        kwh(self.motor - self.regenerated + self.standby)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::energy::*;
+
+    #[test]
+    fn counterweight_balances_half_load() {
+        let model = EnergyModel {
+            friction: 0.0,
+            ..EnergyModel::default()
+        };
+        // 1000kg rated load at 50% balance is matched by about 7 passengers.
+        assert!(model.travel(1, 0) == 0.0);
+        assert!(model.travel(-1, 0) > 0.0);
+        assert!(model.travel(1, 12) > 0.0);
+        assert!(model.travel(-1, 12) == 0.0);
+        let regen = EnergyModel {
+            regenerative: true,
+            ..model
+        };
+        assert!(regen.travel(1, 0) < 0.0);
+        assert!(regen.travel(-1, 12) < 0.0);
+    }
+
+    #[test]
+    fn meter_counts_trips() {
+        let model = EnergyModel::default();
+        let mut meter = Meter::default();
+        meter.travel(&model, 1, 0);
+        meter.travel(&model, 1, 0);
+        meter.stop();
+        meter.travel(&model, -1, 3);
+        assert_eq!((meter.trips, meter.floors), (2, 3));
+        assert!(meter.kwh() > 0.0);
+    }
+}
@@ -0,0 +1,74 @@
+use crate::clock::Instant;
+use std::collections::VecDeque;
+
+/// How many events are kept.
+const LOG_LENGTH: usize = 1000;
+
+/// Something that happened in the simulation, such as a lift's doors
+/// opening or a passenger being assigned a lift.
+#[derive(Debug, Clone)]
+pub struct Event {
+    pub time: Instant,
+    /// The lift the event is about, if any.
+    pub lift: Option<u32>,
+    pub message: String,
+}
+
+/// The most recent events, oldest first.
+#[derive(Debug, Default)]
+pub struct EventLog {
+    events: VecDeque<Event>,
+}
+
+impl EventLog {
+    pub fn push(&mut self, lift: Option<u32>, message: String) {
+        if self.events.len() == LOG_LENGTH {
+            self.events.pop_front();
+        }
+        self.events.push_back(Event {
+            time: Instant::now(),
+            lift,
+            message,
+        });
+    }
+
+    /// Up to `count` events about `lift`, or about anything if `lift` is
+    /// `None`, skipping the `skip` most recent. The events are oldest first.
+    pub fn recent(&self, lift: Option<u32>, count: usize, skip: usize) -> Vec<Event> {
+        let mut events: Vec<Event> = self
+            .events
+            .iter()
+            .rev()
+            .filter(|e| lift.is_none() || e.lift == lift)
+            .skip(skip)
+            .take(count)
+            .cloned()
+            .collect();
+        events.reverse();
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::events::*;
+
+    #[test]
+    fn filter_by_lift() {
+        let mut log = EventLog::default();
+        log.push(Some(1), "Lift 1 doors open at 3".to_string());
+        log.push(None, "Fire recall".to_string());
+        log.push(Some(2), "Lift 2 doors open at 5".to_string());
+        log.push(Some(1), "Lift 1 doors open at 4".to_string());
+        let messages =
+            |events: Vec<Event>| -> Vec<String> { events.into_iter().map(|e| e.message).collect() };
+        assert_eq!(
+            messages(log.recent(Some(1), 10, 0)),
+            ["Lift 1 doors open at 3", "Lift 1 doors open at 4"]
+        );
+        assert_eq!(
+            messages(log.recent(None, 2, 1)),
+            ["Fire recall", "Lift 2 doors open at 5"]
+        );
+    }
+}
@@ -0,0 +1,134 @@
+use crate::building::Building;
+use crate::clock::Instant;
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Style, Stylize},
+    widgets::Widget,
+};
+use std::{collections::VecDeque, time::Duration};
+
+/// How often the landings are sampled: each sample is one column.
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How many samples are kept, enough for the widest terminal.
+const HISTORY_LENGTH: usize = 500;
+
+/// Snapshots of the people waiting at each landing, taken once every
+/// sample interval.
+#[derive(Debug, Default)]
+pub struct History {
+    samples: VecDeque<Vec<(i32, usize, Duration)>>,
+    last: Option<Instant>,
+}
+
+impl History {
+    /// Take a sample of the landings if one is due.
+    pub fn record(&mut self, building: &Building) -> Result<(), String> {
+        if self
+            .last
+            .is_some_and(|last| last.elapsed() < SAMPLE_INTERVAL)
+        {
+            return Ok(());
+        }
+        self.push(building.landings()?);
+        self.last = Some(Instant::now());
+        Ok(())
+    }
+
+    fn push(&mut self, sample: Vec<(i32, usize, Duration)>) {
+        if self.samples.len() == HISTORY_LENGTH {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+}
+
+/// A row per floor from the top down and a column per sample, newest on
+/// the right. Each cell shows how many people were waiting, coloured by
+/// how long the longest of them had waited.
+#[derive(Debug)]
+pub struct Heatmap<'a> {
+    history: &'a History,
+    lowest: i32,
+    highest: i32,
+}
+
+impl Heatmap<'_> {
+    pub fn new(history: &History, lowest: i32, highest: i32) -> Heatmap<'_> {
+        Heatmap {
+            history,
+            lowest,
+            highest,
+        }
+    }
+}
+
+fn wait_colour(wait: Duration) -> Color {
+    match wait.as_secs() {
+        0..=14 => Color::Green,
+        15..=29 => Color::Yellow,
+        30..=59 => Color::LightRed,
+        _ => Color::Red,
+    }
+}
+
+impl Widget for Heatmap<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let label_width = self
+            .lowest
+            .to_string()
+            .len()
+            .max(self.highest.to_string().len()) as u16;
+        let left = area.left() + label_width + 1;
+        if left >= area.right() {
+            return;
+        }
+        let columns = (area.right() - left) as usize;
+        let start = self.history.samples.len().saturating_sub(columns);
+        let floors = (self.lowest..=self.highest).rev();
+        for (y, floor) in (area.top()..area.bottom()).zip(floors) {
+            buf.set_string(
+                area.left(),
+                y,
+                format!("{:>1$}", floor, label_width as usize),
+                Style::new(),
+            );
+            for (x, sample) in (left..).zip(self.history.samples.range(start..)) {
+                let cell = match sample.binary_search_by_key(&floor, |(f, _, _)| *f) {
+                    Ok(pos) => {
+                        let (_, count, wait) = sample[pos];
+                        let symbol = match count {
+                            1..=9 => count.to_string(),
+                            _ => "+".to_string(),
+                        };
+                        (symbol, Style::new().black().bg(wait_colour(wait)))
+                    }
+                    Err(_) => ("·".to_string(), Style::new().dark_gray()),
+                };
+                buf.set_string(x, y, cell.0, cell.1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::heatmap::*;
+
+    #[test]
+    fn newest_sample_on_the_right() {
+        let mut history = History::default();
+        history.push(vec![(1, 2, Duration::from_secs(5))]);
+        history.push(vec![]);
+        history.push(vec![(0, 12, Duration::from_secs(40))]);
+        let area = Rect::new(0, 0, 5, 2);
+        let mut buf = Buffer::empty(area);
+        Heatmap::new(&history, 0, 1).render(area, &mut buf);
+        let rows: Vec<String> = (0..2)
+            .map(|y| (0..5).map(|x| buf.get(x, y).symbol().to_string()).collect())
+            .collect();
+        assert_eq!(rows, ["1 2··", "0 ··+"]);
+        assert_eq!(buf.get(4, 1).bg, Color::LightRed);
+    }
+}
@@ -0,0 +1,321 @@
+use crossterm::event::KeyCode;
+
+/// Something a key can be bound to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    NewPassenger,
+    Random,
+    RandomLobby,
+    View,
+    Mode,
+    Fault,
+    FireRecall,
+    CarCall,
+    Call,
+    CommandLine,
+    PreviousLift,
+    NextLift,
+    Independent,
+    Pause,
+    Step,
+    Faster,
+    Slower,
+    Debug,
+    Help,
+    LogFilter,
+    LogOlder,
+    LogNewer,
+    ScrollUp,
+    ScrollDown,
+}
+
+/// Each action with its name in the config file, what it does, and its
+/// default keys.
+const ACTIONS: [(Action, &str, &str, &[KeyCode]); 25] = [
+    (
+        Action::Quit,
+        "quit",
+        "Quit the program",
+        &[KeyCode::Char('q'), KeyCode::Esc],
+    ),
+    (
+        Action::NewPassenger,
+        "new-passenger",
+        "Add a passenger between two floors",
+        &[KeyCode::Char(' ')],
+    ),
+    (
+        Action::Random,
+        "random",
+        "Add a passenger between random floors",
+        &[KeyCode::Char('r')],
+    ),
+    (
+        Action::RandomLobby,
+        "random-lobby",
+        "Add a passenger to or from the ground floor",
+        &[KeyCode::Char('R')],
+    ),
+    (
+        Action::View,
+        "view",
+        "Cycle between shafts, bar chart and heatmap",
+        &[KeyCode::Char('v')],
+    ),
+    (
+        Action::Mode,
+        "mode",
+        "Cycle the operating mode",
+        &[KeyCode::Char('m')],
+    ),
+    (
+        Action::Fault,
+        "fault",
+        "Put a fault on a lift or repair it",
+        &[KeyCode::Char('f')],
+    ),
+    (
+        Action::FireRecall,
+        "fire-recall",
+        "Start or end a fire recall",
+        &[KeyCode::Char('F')],
+    ),
+    (
+        Action::CarCall,
+        "car-call",
+        "Make a car call",
+        &[KeyCode::Char('c')],
+    ),
+    (
+        Action::Call,
+        "call",
+        "Add a priority passenger or a group",
+        &[KeyCode::Char('p')],
+    ),
+    (
+        Action::CommandLine,
+        "command-line",
+        "Open the command line",
+        &[KeyCode::Char(':')],
+    ),
+    (
+        Action::PreviousLift,
+        "previous-lift",
+        "Select the previous lift",
+        &[KeyCode::Left],
+    ),
+    (
+        Action::NextLift,
+        "next-lift",
+        "Select the next lift",
+        &[KeyCode::Right],
+    ),
+    (
+        Action::Independent,
+        "independent",
+        "Put the selected lift on independent service",
+        &[KeyCode::Char('i')],
+    ),
+    (
+        Action::Pause,
+        "pause",
+        "Pause or resume the simulation",
+        &[KeyCode::Char('s')],
+    ),
+    (
+        Action::Step,
+        "step",
+        "Pause and move on by half a second",
+        &[KeyCode::Char('.')],
+    ),
+    (
+        Action::Faster,
+        "faster",
+        "Run the simulation faster",
+        &[KeyCode::Char('+'), KeyCode::Char('=')],
+    ),
+    (
+        Action::Slower,
+        "slower",
+        "Run the simulation slower",
+        &[KeyCode::Char('-')],
+    ),
+    (
+        Action::Debug,
+        "debug",
+        "Dump the building's state to stderr",
+        &[KeyCode::Char('d')],
+    ),
+    (
+        Action::Help,
+        "help",
+        "Show or hide this help",
+        &[KeyCode::Char('?')],
+    ),
+    (
+        Action::LogFilter,
+        "log-filter",
+        "Show events for the selected lift only, or for all",
+        &[KeyCode::Char('l')],
+    ),
+    (
+        Action::LogOlder,
+        "log-older",
+        "Scroll the event log back",
+        &[KeyCode::PageUp],
+    ),
+    (
+        Action::LogNewer,
+        "log-newer",
+        "Scroll the event log forward",
+        &[KeyCode::PageDown],
+    ),
+    (
+        Action::ScrollUp,
+        "scroll-up",
+        "Scroll the shafts and heatmap up the building",
+        &[KeyCode::Up],
+    ),
+    (
+        Action::ScrollDown,
+        "scroll-down",
+        "Scroll the shafts and heatmap down the building",
+        &[KeyCode::Down],
+    ),
+];
+
+/// Which key does what.
+#[derive(Debug, Clone)]
+pub struct KeyMap {
+    bindings: Vec<(KeyCode, Action)>,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        let bindings = ACTIONS
+            .iter()
+            .flat_map(|(action, _, _, keys)| keys.iter().map(|key| (*key, *action)))
+            .collect();
+        KeyMap { bindings }
+    }
+}
+
+impl KeyMap {
+    pub fn action(&self, key: KeyCode) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, action)| *action)
+    }
+
+    /// Bind an action, by its name in the config file, to a list of keys
+    /// separated by spaces, as in `quit = x esc`. The keys replace the
+    /// action's old ones and are taken from any other action they were
+    /// bound to.
+    pub fn bind(&mut self, name: &str, keys: &str) -> Result<(), String> {
+        let (action, _, _, _) = ACTIONS
+            .iter()
+            .find(|(_, n, _, _)| *n == name)
+            .ok_or(format!("Unknown action '{}'", name))?;
+        let keys = keys
+            .split_whitespace()
+            .map(parse_key)
+            .collect::<Result<Vec<_>, _>>()?;
+        if keys.is_empty() {
+            return Err(format!("No keys given for '{}'", name));
+        }
+        self.bindings
+            .retain(|(k, a)| a != action && !keys.contains(k));
+        self.bindings
+            .extend(keys.into_iter().map(|key| (key, *action)));
+        Ok(())
+    }
+
+    /// The keys, config name and description of every action, as shown in
+    /// the help overlay. Actions with no keys left are skipped.
+    pub fn help(&self) -> Vec<(String, &'static str, &'static str)> {
+        ACTIONS
+            .iter()
+            .filter_map(|(action, name, description, _)| {
+                let keys: Vec<String> = self
+                    .bindings
+                    .iter()
+                    .filter(|(_, a)| a == action)
+                    .map(|(key, _)| key_name(*key))
+                    .collect();
+                (!keys.is_empty()).then(|| (keys.join(", "), *name, *description))
+            })
+            .collect()
+    }
+}
+
+/// A key as written in the config file: a single character, or one of
+/// space, esc, tab, enter, backspace, left, right, up, down, pageup,
+/// pagedown, and f1 to f12.
+fn parse_key(name: &str) -> Result<KeyCode, String> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Ok(KeyCode::Char(c));
+    }
+    match name.to_lowercase().as_str() {
+        "space" => Ok(KeyCode::Char(' ')),
+        "esc" => Ok(KeyCode::Esc),
+        "tab" => Ok(KeyCode::Tab),
+        "enter" => Ok(KeyCode::Enter),
+        "backspace" => Ok(KeyCode::Backspace),
+        "left" => Ok(KeyCode::Left),
+        "right" => Ok(KeyCode::Right),
+        "up" => Ok(KeyCode::Up),
+        "down" => Ok(KeyCode::Down),
+        "pageup" => Ok(KeyCode::PageUp),
+        "pagedown" => Ok(KeyCode::PageDown),
+        f => f
+            .strip_prefix('f')
+            .and_then(|n| n.parse::<u8>().ok())
+            .filter(|n| (1..=12).contains(n))
+            .map(KeyCode::F)
+            .ok_or(format!("Unknown key '{}'", name)),
+    }
+}
+
+fn key_name(key: KeyCode) -> String {
+    match key {
+        KeyCode::Char(' ') => "space".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::Esc => "esc".to_string(),
+        KeyCode::Tab => "tab".to_string(),
+        KeyCode::Enter => "enter".to_string(),
+        KeyCode::Backspace => "backspace".to_string(),
+        KeyCode::Left => "left".to_string(),
+        KeyCode::Right => "right".to_string(),
+        KeyCode::Up => "up".to_string(),
+        KeyCode::Down => "down".to_string(),
+        KeyCode::PageUp => "pageup".to_string(),
+        KeyCode::PageDown => "pagedown".to_string(),
+        KeyCode::F(n) => format!("f{}", n),
+        other => format!("{:?}", other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::keys::*;
+
+    #[test]
+    fn rebind_keys() {
+        let mut keys = KeyMap::default();
+        assert_eq!(keys.action(KeyCode::Char('q')), Some(Action::Quit));
+        // A Dvorak user moves quit and takes 'r' from random.
+        keys.bind("quit", "' esc").unwrap();
+        keys.bind("pause", "r").unwrap();
+        assert_eq!(keys.action(KeyCode::Char('q')), None);
+        assert_eq!(keys.action(KeyCode::Char('\'')), Some(Action::Quit));
+        assert_eq!(keys.action(KeyCode::Char('r')), Some(Action::Pause));
+        assert_eq!(keys.action(KeyCode::Char('s')), None);
+        assert!(!keys.help().iter().any(|(_, name, _)| *name == "random"));
+        assert!(keys.bind("teleport", "t").is_err());
+        assert!(keys.bind("quit", "hyper").is_err());
+        assert_eq!(parse_key("F5"), Ok(KeyCode::F(5)));
+    }
+}
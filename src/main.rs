@@ -1,52 +1,100 @@
+use clap::Parser;
 use crossterm::{
-    event::{self, KeyCode, KeyEventKind},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, KeyCode, KeyEventKind, MouseButton,
+        MouseEventKind,
+    },
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     ExecutableCommand,
 };
 use ratatui::{
     backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout, Rect},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
     prelude::Stylize,
     style::{Color, Style},
-    widgets::{BarChart, Block, Borders},
+    text::{Line, Span},
+    widgets::{
+        block::{Position, Title},
+        BarChart, Block, Borders, Clear, Paragraph,
+    },
     Terminal,
 };
-use std::io::{stdout, Result};
-use clap::Parser;
+use std::{
+    io::{stdout, Result},
+    sync::Arc,
+    time::Duration,
+};
 use tui_textarea::{Input, Key, TextArea};
 
 mod building;
+mod clock;
+mod config;
+mod dashboard;
+mod detail;
+mod energy;
+mod events;
+mod heatmap;
+mod keys;
+mod report;
+mod scenario;
+mod shaft;
+mod traffic;
+
+use building::{Bank, BankSpec, Behaviour, Building, Dispatch, Mode, Parking, Passenger};
+use config::Config;
+use dashboard::Dashboard;
+use detail::DetailView;
+use energy::{kwh, EnergyModel};
+use heatmap::{Heatmap, History};
+use keys::{Action, KeyMap};
+use scenario::{completions, Command, Scenario};
+use shaft::{Hit, ShaftView};
+
+/// Width of the side panels showing the dashboard and the selected lift.
+const DETAIL_WIDTH: u16 = 32;
+
+/// Height of the event log pane, borders included.
+const LOG_HEIGHT: u16 = 8;
 
-use building::{Building, Passenger};
+/// Terminals narrower or shorter than this are drawn in compact mode, with
+/// narrow shafts and no side panels or event log.
+const COMPACT_WIDTH: u16 = 80;
+const COMPACT_HEIGHT: u16 = 24;
 
-/// helper function to create a centered rect using up certain percentage of the available rect `r`
+/// Floors moved by each press of the scroll keys.
+const SCROLL_FLOORS: i32 = 5;
+
+/// The narrowest a bar in the bar chart is drawn.
+const MIN_BAR_WIDTH: usize = 4;
+
+/// How soon after one digit another must be typed for the two to make up
+/// a single lift number.
+const DIGIT_DELAY: Duration = Duration::from_millis(1000);
+
+/// A box for a one-line popup and its border in the middle of `r`,
+/// `percent_x` percent as wide as it, shrunk to fit.
 fn centered_line(percent_x: u16, r: Rect) -> Rect {
-    // Cut the given rectangle into three vertical pieces
-    let popup_height = 3;
-    let popup_perc = (((popup_height as f64) / (r.height as f64)) * (100 as f64)).round() as u16;
-    let popup_layout = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([
-            Constraint::Percentage((100 - popup_perc) / 2),
-            Constraint::Length(popup_height),
-            Constraint::Percentage((100 - popup_perc) / 2),
-        ])
-        .split(r);
-
-    // Then cut the middle vertical piece into three width-wise pieces
-    Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([
-            Constraint::Percentage((100 - percent_x) / 2),
-            Constraint::Percentage(percent_x),
-            Constraint::Percentage((100 - percent_x) / 2),
-        ])
-        .split(popup_layout[1])[1] // Return the middle chunk
+    let width = (r.width as u32 * percent_x.min(100) as u32 / 100) as u16;
+    centered_rect(width, 3, r)
+}
+
+/// A rect of the given size in the middle of `r`, shrunk to fit.
+fn centered_rect(width: u16, height: u16, r: Rect) -> Rect {
+    let width = width.min(r.width);
+    let height = height.min(r.height);
+    Rect::new(
+        r.x + (r.width - width) / 2,
+        r.y + (r.height - height) / 2,
+        width,
+        height,
+    )
 }
 
 /// Program to simulate a building with lifts
 ///
-/// While the program is running, the following keybindings are in effect:
+/// While the program is running, the following keybindings are in effect
+/// by default. <?> lists the bindings in effect, which can be changed in
+/// the [keys] section of a config file.
 ///
 /// <q>:     Quit the program.
 ///
@@ -56,6 +104,65 @@ fn centered_line(percent_x: u16, r: Rect) -> Rect {
 ///
 /// <R>:     Add a new passenger going between a random floor and the ground
 ///          floor.
+///
+/// <v>:     Cycle between the shaft view, a bar chart of the lifts' floors
+///          and a heatmap of the people waiting at each floor over time.
+///
+/// <m>:     Cycle the operating mode between automatic, balanced, up-peak
+///          and down-peak.
+///
+/// <f>:     Bring up a dialog box to put a fault on a lift, as in
+///          "2 breakdown", or to repair it, as in "2 repair". The faults
+///          are out, breakdown and doors.
+///
+/// <F>:     Start or end a fire recall, sending every lift to its lobby.
+///
+/// <c>:     Bring up a dialog box to make a car call, as in "2 7". During a
+///          fire recall, the first lift given a car call is put on
+///          firefighter service.
+///
+/// <p>:     Bring up a dialog box to add a priority passenger or a group, as
+///          in "0 12 vip" or "3 0 x12". The classes are accessibility, vip
+///          and emergency.
+///
+/// <:>:     Open the command line, which takes any scenario command, as in
+///          "call 0 5 x10", "fail lift 2", "speed 10", "strategy etd",
+///          "seed 42" or "save run.json". <up>/<down> go through earlier
+///          commands and <tab> completes words. Any command that fails is
+///          noted in the event log with the reason.
+///
+/// Click a landing in the shaft view to pick where a new passenger starts
+/// from, then click another to send them there. Click a lift to select it.
+///
+/// <left>/<right>: Select the previous or next lift, whose full state is
+///          shown in the side panel.
+///
+/// <0>-<9>: Select a lift by number. Digits typed within a second of each
+///          other make up one number, as in <1><2> for lift 12.
+///
+/// <i>:     Put the selected lift on independent service, where it answers
+///          car calls only, or give it back to group control.
+///
+/// <l>:     Show only the selected lift's events in the event log, or go
+///          back to showing everything.
+///
+/// <pageup>/<pagedown>: Scroll the event log back or forward.
+///
+/// <up>/<down>: Scroll the shafts and heatmap up or down the building.
+///          Banks too tall or too wide for the terminal are otherwise shown
+///          a page at a time, turned to the selected lift. On terminals
+///          smaller than 80x24 the side panels and event log are left out
+///          and the shafts are drawn narrower.
+///
+/// <?>:     Show or hide the key bindings, which <up>/<down> scroll
+///          through on small terminals.
+///
+/// <s>:     Pause or resume the simulation.
+///
+/// <.>:     Pause the simulation and move it on by half a second.
+///
+/// <+>/<->: Run the simulation faster or slower, from 0.25x to 100x real
+///          time.
 #[derive(Parser, Debug)]
 #[command(version, about, long_about)]
 struct Args {
@@ -70,6 +177,66 @@ struct Args {
     /// Number of lifts in the building
     #[arg(short, long, default_value_t = 5)]
     lifts: u32,
+
+    /// Comma-separated sky lobby floors, where passengers change between
+    /// lifts serving the zones above and below
+    #[arg(short, long, value_delimiter = ',')]
+    sky_lobbies: Vec<i32>,
+
+    /// Use double-deck cars, whose decks serve alternate floors
+    #[arg(short = 'D', long)]
+    double_deck: bool,
+
+    /// A separate lift bank, as NAME:LIFTS[x2][:LOWEST:HIGHEST[:LOBBY]],
+    /// where x2 gives the bank double-deck cars. May be given more than
+    /// once, in which case --lifts, --sky-lobbies and --double-deck are
+    /// ignored
+    #[arg(long = "bank")]
+    banks: Vec<BankSpec>,
+
+    /// What the dispatchers minimise when assigning a lift to a call
+    #[arg(long, value_enum, default_value_t = Dispatch::Wait)]
+    dispatch: Dispatch,
+
+    /// Where lifts wait once they have nothing left to do
+    #[arg(long, value_enum, default_value_t = Parking::Stay)]
+    parking: Parking,
+
+    /// Operating mode of the group controller. If not given, the mode is
+    /// chosen automatically from the recent traffic
+    #[arg(long, value_enum)]
+    mode: Option<Mode>,
+
+    /// Give every lift a regenerative drive, which returns energy to the
+    /// supply when the load pulls the car
+    #[arg(long)]
+    regenerative: bool,
+
+    /// Seconds a passenger will wait for a lift before giving up and taking
+    /// the stairs. If not given, passengers wait for as long as it takes
+    #[arg(long)]
+    patience: Option<f64>,
+
+    /// Trips of this many floors or fewer may be walked instead of taking a
+    /// lift
+    #[arg(long, default_value_t = 0)]
+    stairs: i32,
+
+    /// The share of short trips, from 0 to 1, that are walked
+    #[arg(long, default_value_t = 0.5)]
+    stairs_share: f64,
+
+    /// A file of timed commands to run, one per line, as in
+    /// "12.5 fault 2 breakdown", "20 repair 2", "30 recall 0",
+    /// "35 car 1 8" or "60 recall off"
+    #[arg(long)]
+    scenario: Option<String>,
+
+    /// A config file. Its [keys] section rebinds actions to keys, one per
+    /// line, as in "quit = x esc" or "pause = space". The action names are
+    /// listed in the help overlay
+    #[arg(long)]
+    config: Option<String>,
 }
 
 #[derive(Debug)]
@@ -77,7 +244,55 @@ struct UI<'a> {
     state: UIState,
     from_floor: Option<i32>,
     to_floor: Option<i32>,
+    floor_placeholder: String,
     textarea: TextArea<'a>,
+    selected: u32,
+    view: View,
+    /// The people waiting at each floor over time, for the heatmap.
+    history: History,
+    /// A landing clicked as the start of a call, waiting for a click on the
+    /// destination.
+    origin: Option<i32>,
+    /// Where each bank's shaft view was last drawn, by bank.
+    shaft_areas: Vec<(usize, Rect)>,
+    /// Lines run from the command line, oldest first.
+    commands: Vec<String>,
+    /// Which of `commands` is shown while going back through them.
+    browsing: Option<usize>,
+    keys: KeyMap,
+    /// Whether the key bindings are being shown.
+    help: bool,
+    /// Lines the key bindings are scrolled down.
+    help_scroll: u16,
+    /// The lift number typed so far and when its last digit was typed.
+    typed: Option<(u32, std::time::Instant)>,
+    /// Whether the event log shows only the selected lift's events.
+    log_filtered: bool,
+    /// How many of the newest events the log is scrolled back past.
+    log_scroll: usize,
+    /// Floors the shafts and heatmap are scrolled up, or down if negative.
+    floor_scroll: i32,
+    /// Whether the terminal was small enough for compact mode when last
+    /// drawn.
+    compact: bool,
+}
+
+/// How the lifts are drawn.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+enum View {
+    Shafts,
+    BarChart,
+    Heatmap,
+}
+
+impl View {
+    fn next(self) -> View {
+        match self {
+            View::Shafts => View::BarChart,
+            View::BarChart => View::Heatmap,
+            View::Heatmap => View::Shafts,
+        }
+    }
 }
 
 #[derive(PartialEq, Eq, Debug)]
@@ -85,56 +300,137 @@ enum UIState {
     BarChart,
     FromFloorPopup,
     ToFloorPopup,
+    CommandPopup(Prompt),
+}
+
+/// A dialog box that takes a whole command on one line.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+enum Prompt {
+    Fault,
+    CarCall,
+    Call,
+    /// Any command, as in a scenario file.
+    CommandLine,
+}
+
+impl Prompt {
+    fn title(&self) -> &'static str {
+        match self {
+            Prompt::Fault => "Lift and fault, or repair:",
+            Prompt::CarCall => "Car call:",
+            Prompt::Call => "Call:",
+            Prompt::CommandLine => ":",
+        }
+    }
+
+    fn placeholder(&self) -> &'static str {
+        match self {
+            Prompt::Fault => "Enter a lift number and out, breakdown, doors or repair",
+            Prompt::CarCall => "Enter a lift number and a floor",
+            Prompt::Call => "Enter two floors, then a class or xN for a group of N",
+            Prompt::CommandLine => "Enter a command, as in call 3 12 or speed 10",
+        }
+    }
+
+    /// Parse the dialog's input as a command: "LIFT FAULT" or "LIFT repair"
+    /// for a fault, "LIFT FLOOR" for a car call, and "FROM TO [CLASS] [xN]"
+    /// for a call. The command line takes any command.
+    fn command(&self, input: &str) -> std::result::Result<Command, String> {
+        let words = input.split_whitespace().collect::<Vec<_>>();
+        match (self, &words[..]) {
+            (Prompt::Fault, [lift, "repair"]) => format!("repair {}", lift).parse(),
+            (Prompt::Fault, [lift, fault]) => format!("fault {} {}", lift, fault).parse(),
+            (Prompt::Fault, _) => Err("Expected a lift number and a fault".to_string()),
+            (Prompt::CarCall, [lift, floor]) => format!("car {} {}", lift, floor).parse(),
+            (Prompt::CarCall, _) => Err("Expected a lift number and a floor".to_string()),
+            (Prompt::Call, [_, _, ..]) => format!("call {}", input).parse(),
+            (Prompt::Call, _) => Err("Expected two floors".to_string()),
+            (Prompt::CommandLine, _) => input.parse(),
+        }
+    }
 }
 
 impl UI<'_> {
-    fn new(building: &Building) -> UI {
+    fn new(building: &Building, keys: KeyMap) -> UI<'_> {
         let mut textarea = TextArea::default();
         textarea.set_cursor_line_style(Style::default());
-        textarea.set_placeholder_text(format!(
+        let floor_placeholder = format!(
             "Enter a floor number from {} to {}",
             building.bottom_floor, building.top_floor
-        ));
+        );
+        textarea.set_placeholder_text(floor_placeholder.clone());
         textarea.set_block(
             Block::default()
                 .borders(Borders::ALL)
                 .style(Style::default().bg(Color::Black).fg(Color::White))
-                .title(format!("Going from floor:")),
+                .title("Going from floor:"),
         );
         UI {
             state: UIState::BarChart,
             from_floor: None,
             to_floor: None,
+            floor_placeholder,
             textarea,
+            selected: 0,
+            view: View::Shafts,
+            history: History::default(),
+            origin: None,
+            shaft_areas: Vec::new(),
+            commands: Vec::new(),
+            browsing: None,
+            keys,
+            help: false,
+            help_scroll: 0,
+            typed: None,
+            log_filtered: false,
+            log_scroll: 0,
+            floor_scroll: 0,
+            compact: false,
         }
     }
 
     fn validate(&mut self, building: &Building) -> bool {
-        let mut title = String::new();
+        let mut title;
         let happy_title = self.popup_title();
-        let mut result = false;
-        match self.popup_input().parse::<i32>() {
-            Err(err) => {
-                self.textarea
-                    .set_style(Style::default().fg(Color::LightRed));
-                title = format!("ERROR: {}", err);
-                result = false;
-            }
-            Ok(val) => {
-                if val < building.bottom_floor || val > building.top_floor {
+        let result;
+        if let UIState::CommandPopup(prompt) = self.state {
+            match prompt.command(&self.popup_input()) {
+                Err(err) => {
                     self.textarea
                         .set_style(Style::default().fg(Color::LightRed));
-                    title = format!(
-                        "ERROR: Floor must be between {} and {}.",
-                        building.bottom_floor, building.top_floor
-                    );
+                    title = format!("ERROR: {}", err);
                     result = false;
-                } else {
+                }
+                Ok(_) => {
                     title = happy_title.clone();
                     self.textarea.set_style(Style::default().fg(Color::White));
                     result = true;
                 }
             }
+        } else {
+            match self.popup_input().parse::<i32>() {
+                Err(err) => {
+                    self.textarea
+                        .set_style(Style::default().fg(Color::LightRed));
+                    title = format!("ERROR: {}", err);
+                    result = false;
+                }
+                Ok(val) => {
+                    if val < building.bottom_floor || val > building.top_floor {
+                        self.textarea
+                            .set_style(Style::default().fg(Color::LightRed));
+                        title = format!(
+                            "ERROR: Floor must be between {} and {}.",
+                            building.bottom_floor, building.top_floor
+                        );
+                        result = false;
+                    } else {
+                        title = happy_title.clone();
+                        self.textarea.set_style(Style::default().fg(Color::White));
+                        result = true;
+                    }
+                }
+            }
         }
         if self.textarea.is_empty() {
             title = happy_title.clone();
@@ -149,24 +445,158 @@ impl UI<'_> {
     }
 
     fn reset(&mut self) {
+        if let UIState::CommandPopup(_) = self.state {
+            self.textarea
+                .set_placeholder_text(self.floor_placeholder.clone());
+        }
         self.state = UIState::BarChart;
         self.from_floor = None;
         self.to_floor = None;
+        self.browsing = None;
         self.clear_input();
     }
 
+    fn set_input(&mut self, input: &str) {
+        self.clear_input();
+        self.textarea.insert_str(input);
+    }
+
+    /// Show the previous or next line from the command line's history.
+    fn browse(&mut self, back: bool) {
+        let count = self.commands.len();
+        self.browsing = match (self.browsing, back) {
+            (None, true) if count > 0 => Some(count - 1),
+            (Some(i), true) => Some(i.saturating_sub(1)),
+            (Some(i), false) if i + 1 < count => Some(i + 1),
+            _ => None,
+        };
+        match self.browsing {
+            Some(i) => self.set_input(&self.commands[i].clone()),
+            None => self.clear_input(),
+        }
+    }
+
+    /// Finish the word being typed on the command line as far as the
+    /// possible words agree, listing them in the title if there are more
+    /// than one. Returns whether the input is then valid.
+    fn complete(&mut self, building: &Building) -> bool {
+        let input = self.popup_input();
+        let candidates = completions(&input);
+        let partial = match input.ends_with(char::is_whitespace) {
+            true => "",
+            false => input.split_whitespace().last().unwrap_or(""),
+        };
+        if let Some(first) = candidates.first() {
+            let common = candidates.iter().fold(*first, |common, c| {
+                let len = common
+                    .chars()
+                    .zip(c.chars())
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                &common[..len]
+            });
+            self.textarea.insert_str(&common[partial.len()..]);
+            if candidates.len() == 1 {
+                self.textarea.insert_char(' ');
+            }
+        }
+        let valid = self.validate(building);
+        if candidates.len() > 1 {
+            self.textarea.set_block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .style(Style::default().bg(Color::Black).fg(Color::White))
+                    .title(format!(": {}", candidates.join(" "))),
+            );
+        }
+        valid
+    }
+
     fn clear_input(&mut self) {
         self.textarea.select_all();
         self.textarea.delete_char();
     }
 
+    /// Run the dialog's input as a command, logging why if it fails.
+    fn run_command(&mut self, prompt: Prompt, building: &Building) {
+        let input = self.popup_input();
+        if input.trim().is_empty() {
+            return;
+        }
+        let result = prompt
+            .command(&input)
+            .and_then(|command| command.apply(building));
+        if let Err(e) = result {
+            let _ = building.log(None, format!("{}: {}", input.trim(), e));
+        }
+        if prompt == Prompt::CommandLine && self.commands.last() != Some(&input) {
+            self.commands.push(input);
+        }
+    }
+
     fn call_lift(&self, building: &Building) {
-        building
-            .respond(Passenger::new(
-                self.from_floor.unwrap(),
-                self.to_floor.unwrap(),
-            ))
-            .unwrap();
+        let _ = building.respond(Passenger::new(
+            self.from_floor.unwrap(),
+            self.to_floor.unwrap(),
+        ));
+    }
+
+    /// Select a lift and scroll back to it.
+    fn select(&mut self, id: u32) {
+        self.selected = id;
+        self.floor_scroll = 0;
+    }
+
+    /// Select a lift by a digit of its number. A digit typed soon after
+    /// another adds to the number, as long as there is a lift with the
+    /// longer number; otherwise it starts a new one.
+    fn type_digit(&mut self, digit: u32, lift_count: u32) {
+        let longer = self
+            .typed
+            .filter(|(_, at)| at.elapsed() < DIGIT_DELAY)
+            .map(|(id, _)| id.saturating_mul(10).saturating_add(digit))
+            .filter(|id| *id < lift_count);
+        let id = longer.unwrap_or(digit);
+        self.typed = Some((id, std::time::Instant::now()));
+        if id < lift_count {
+            self.select(id);
+        }
+    }
+
+    /// Scroll the shafts and heatmap up or down, no further than the height
+    /// of the building.
+    fn scroll(&mut self, building: &Building, up: bool) {
+        let limit = building.max_value() as i32;
+        let floors = if up { SCROLL_FLOORS } else { -SCROLL_FLOORS };
+        self.floor_scroll = (self.floor_scroll + floors).clamp(-limit, limit);
+    }
+
+    fn shaft_view(&self, bank: &Bank) -> std::result::Result<ShaftView, String> {
+        Ok(ShaftView::new(bank, self.selected, self.origin)?
+            .scroll(self.floor_scroll)
+            .compact(self.compact))
+    }
+
+    /// Act on a left click in a shaft view: pick a call's origin or
+    /// destination, or select a lift.
+    fn click(&mut self, building: &Building, x: u16, y: u16) {
+        for (bank, area) in self.shaft_areas.clone() {
+            let Ok(view) = self.shaft_view(&building.banks()[bank]) else {
+                continue;
+            };
+            match (view.hit(area, x, y), self.origin) {
+                (Some(Hit::Lift(id)), _) => self.select(id),
+                (Some(Hit::Landing(floor)), None) => self.origin = Some(floor),
+                (Some(Hit::Landing(floor)), Some(origin)) => {
+                    if floor != origin {
+                        let _ = building.respond(Passenger::new(origin, floor));
+                    }
+                    self.origin = None;
+                }
+                (None, _) => continue,
+            }
+            return;
+        }
     }
 
     fn popup_title(&self) -> String {
@@ -174,17 +604,36 @@ impl UI<'_> {
             UIState::ToFloorPopup => {
                 format!("Going from floor {} to:", self.from_floor.unwrap_or(0))
             }
+            UIState::CommandPopup(prompt) => prompt.title().to_string(),
             _ => "Going from floor:".to_string(),
         }
     }
 
-    fn popup_active(&self) -> bool {
+    fn prompt(&self) -> Option<Prompt> {
         match self.state {
-            UIState::FromFloorPopup | UIState::ToFloorPopup => true,
-            _ => false,
+            UIState::CommandPopup(prompt) => Some(prompt),
+            _ => None,
         }
     }
 
+    fn popup_active(&self) -> bool {
+        matches!(
+            self.state,
+            UIState::FromFloorPopup | UIState::ToFloorPopup | UIState::CommandPopup(_)
+        )
+    }
+
+    fn open_prompt(&mut self, prompt: Prompt) {
+        self.state = UIState::CommandPopup(prompt);
+        self.textarea.set_placeholder_text(prompt.placeholder());
+        self.textarea.set_block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().bg(Color::Black).fg(Color::White))
+                .title(self.popup_title()),
+        );
+    }
+
     fn set_floor(&mut self) {
         let floor = str::parse::<i32>(&self.popup_input()).unwrap();
         match self.from_floor {
@@ -216,20 +665,56 @@ impl UI<'_> {
         match self.state {
             UIState::BarChart => self.state = UIState::FromFloorPopup,
             UIState::FromFloorPopup => self.state = UIState::ToFloorPopup,
-            UIState::ToFloorPopup => self.reset(),
+            UIState::ToFloorPopup | UIState::CommandPopup(_) => self.reset(),
         }
     }
 }
 
 fn main() -> Result<()> {
     let args = Args::parse();
+    let scenario = match &args.scenario {
+        Some(path) => Scenario::load(path)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?,
+        None => Scenario::default(),
+    };
+    let config = match &args.config {
+        Some(path) => Config::load(path)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?,
+        None => Config::default(),
+    };
+    let banks = if args.banks.is_empty() {
+        let decks = if args.double_deck { 2 } else { 1 };
+        BankSpec::zoned(args.bottom, args.top, args.lifts, decks, &args.sky_lobbies)
+    } else {
+        args.banks
+    };
+    for bank in &banks {
+        bank.check(args.bottom, args.top)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    }
     stdout().execute(EnterAlternateScreen)?;
+    stdout().execute(EnableMouseCapture)?;
     enable_raw_mode()?;
     let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
     terminal.clear()?;
 
     // let building = Arc::new(Building::new(0, 15, 1));
-    let building = Building::new(args.bottom, args.top, args.lifts);
+    let energy = EnergyModel {
+        regenerative: args.regenerative,
+        ..EnergyModel::default()
+    };
+    let building = Building::new(args.bottom, args.top, &banks, energy);
+    building.set_dispatch(args.dispatch).unwrap();
+    building.set_parking(args.parking).unwrap();
+    building.set_mode(args.mode).unwrap();
+    building
+        .set_behaviour(Behaviour {
+            patience: args.patience.map(|s| Duration::from_secs_f64(s.max(0.0))),
+            stairs_floors: args.stairs,
+            stairs_share: args.stairs_share,
+        })
+        .unwrap();
+    scenario.start(Arc::clone(&building));
 
     // let new_build = building.clone();
     // thread::spawn(move || {
@@ -258,35 +743,235 @@ fn main() -> Result<()> {
     //     .direction(Direction::Horizontal)
     //     .constraints([Constraint::Percentage(70), Constraint::Percentage(30)].as_ref());
 
-    let mut ui = UI::new(&building);
+    let mut ui = UI::new(&building, config.keys);
     let mut is_valid = false;
     loop {
         terminal.draw(|frame| {
-            let area = frame.size();
-            frame.render_widget(
-                BarChart::default()
-                    .block(Block::default().title("Lifts").borders(Borders::ALL))
-                    .bar_width(bar_width(&area, building.lift_count()))
-                    .bar_gap(1)
-                    .bar_style(Style::new().green().on_blue())
-                    .value_style(Style::new().blue().bold())
-                    .label_style(Style::new().white())
-                    .data(building.data().unwrap())
-                    .max(building.max_value()),
-                area,
-            );
+            let block = Block::default()
+                .title(chart_title(&building))
+                .title(Title::from(speed_title()).alignment(Alignment::Right))
+                .title(Title::from(traffic_title(&building)).position(Position::Bottom))
+                .borders(Borders::ALL);
+            let size = frame.size();
+            ui.compact = size.width < COMPACT_WIDTH || size.height < COMPACT_HEIGHT;
+            // A small terminal leaves out the side panels and event log.
+            let (side_width, log_height) = if ui.compact {
+                (0, 0)
+            } else {
+                (DETAIL_WIDTH, LOG_HEIGHT)
+            };
+            let columns = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Min(0), Constraint::Length(side_width)])
+                .split(block.inner(size));
+            let rows = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(0), Constraint::Length(log_height)])
+                .split(columns[0]);
+            let area = rows[0];
+            frame.render_widget(block, size);
+            if !ui.compact {
+                // The panel is as wide as the sparkline has room for.
+                let dashboard = building
+                    .stats(DETAIL_WIDTH as usize - 2)
+                    .map(Dashboard::new)
+                    .ok();
+                // With many lifts, leave room for the selected lift too.
+                let height = dashboard
+                    .as_ref()
+                    .map_or(0, |d| (d.height() + 2).min(columns[1].height / 2));
+                let side = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Length(height), Constraint::Min(0)])
+                    .split(columns[1]);
+                if let Some(dashboard) = dashboard {
+                    let block = Block::default().title("Dashboard").borders(Borders::ALL);
+                    let inner = block.inner(side[0]);
+                    frame.render_widget(block, side[0]);
+                    frame.render_widget(dashboard, inner);
+                }
+                let block = Block::default()
+                    .title("Selected lift")
+                    .borders(Borders::ALL);
+                let inner = block.inner(side[1]);
+                frame.render_widget(block, side[1]);
+                if let Ok(detail) = building.detail(ui.selected) {
+                    frame.render_widget(DetailView::new(detail), inner);
+                }
+                let filter = ui.log_filtered.then_some(ui.selected);
+                let block = Block::default().borders(Borders::ALL);
+                let inner = block.inner(rows[1]);
+                let count = inner.height as usize;
+                let mut events = building
+                    .events(filter, count, ui.log_scroll)
+                    .unwrap_or_default();
+                if events.len() < count && ui.log_scroll > 0 {
+                    // Scrolled back past the oldest event, so show a full page.
+                    ui.log_scroll = ui.log_scroll.saturating_sub(count - events.len());
+                    events = building
+                        .events(filter, count, ui.log_scroll)
+                        .unwrap_or_default();
+                }
+                let lines: Vec<Line> = events
+                    .into_iter()
+                    .map(|event| {
+                        Line::from(vec![
+                            Span::from(format!(
+                                "{:>7.1}s ",
+                                event.time.since_start().as_secs_f64()
+                            ))
+                            .dark_gray(),
+                            Span::from(event.message),
+                        ])
+                    })
+                    .collect();
+                frame.render_widget(block.title(log_title(filter, ui.log_scroll)), rows[1]);
+                frame.render_widget(Paragraph::new(lines), inner);
+            }
+            let _ = ui.history.record(&building);
+            ui.shaft_areas.clear();
+            if ui.view == View::Heatmap {
+                let block = Block::default()
+                    .title(format!(
+                        "Waiting by floor, a column every {}s, coloured by longest wait",
+                        heatmap::SAMPLE_INTERVAL.as_secs()
+                    ))
+                    .borders(Borders::ALL);
+                let inner = block.inner(area);
+                frame.render_widget(block, area);
+                let (bottom, top) = (building.bottom_floor, building.top_floor);
+                let top = shaft::top_floor(bottom, top, inner.height, top, ui.floor_scroll);
+                let bottom = bottom.max(top - inner.height as i32 + 1);
+                frame.render_widget(Heatmap::new(&ui.history, bottom, top), inner);
+            } else {
+                let banks = building.banks();
+                let total: u32 = banks.iter().map(|b| b.lift_count().max(1) as u32).sum();
+                let panels = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints(
+                        banks
+                            .iter()
+                            .map(|b| Constraint::Ratio(b.lift_count().max(1) as u32, total))
+                            .collect::<Vec<_>>(),
+                    )
+                    .split(area);
+                for (i, (bank, area)) in banks.iter().zip(panels.iter()).enumerate() {
+                    let title = format!("{} (lobby {})", bank.name, bank.lobby);
+                    if ui.view == View::Shafts {
+                        let Ok(view) = ui.shaft_view(bank) else {
+                            continue;
+                        };
+                        let block = Block::default().borders(Borders::ALL);
+                        let inner = block.inner(*area);
+                        let title = match view.scrolled(inner) {
+                            Some(window) => format!("{}, {}", title, window),
+                            None => title,
+                        };
+                        let title = match ui.origin {
+                            Some(floor) => format!("{}, from {} to?", title, floor),
+                            None => title,
+                        };
+                        frame.render_widget(block.title(title), *area);
+                        frame.render_widget(view, inner);
+                        ui.shaft_areas.push((i, inner));
+                        continue;
+                    }
+                    let cars = bank.cars().unwrap_or_default();
+                    let (width, fit) = bar_layout(area, cars.len());
+                    let selected = cars.iter().position(|car| car.id == ui.selected);
+                    let first = shaft::first_lift(selected, fit, cars.len());
+                    let lifts = first..(first + fit).min(cars.len());
+                    let title = if lifts.len() < cars.len() {
+                        format!(
+                            "{}, lifts {} to {} of {}",
+                            title,
+                            cars[lifts.start].id,
+                            cars[lifts.end - 1].id,
+                            cars.len()
+                        )
+                    } else {
+                        title
+                    };
+                    frame.render_widget(
+                        BarChart::default()
+                            .block(Block::default().title(title).borders(Borders::ALL))
+                            .bar_width(width)
+                            .bar_gap(1)
+                            .bar_style(Style::new().green().on_blue())
+                            .value_style(Style::new().blue().bold())
+                            .label_style(Style::new().white())
+                            .data(building.data(bank, ui.selected, lifts).unwrap())
+                            .max(building.max_value()),
+                        *area,
+                    );
+                }
+            }
 
             if ui.popup_active() {
                 let popup_area = centered_line(60, frame.size());
                 frame.render_widget(ui.textarea.widget(), popup_area);
             }
+
+            if ui.help {
+                let mut lines: Vec<Line> = ui
+                    .keys
+                    .help()
+                    .into_iter()
+                    .map(|(keys, name, description)| {
+                        Line::from(vec![
+                            Span::from(format!("{:>10}", keys)).bold(),
+                            Span::from(format!("  {:<14}", name)).dark_gray(),
+                            Span::from(description),
+                        ])
+                    })
+                    .collect();
+                lines.push(Line::from(vec![
+                    Span::from(format!("{:>10}", "0-9")).bold(),
+                    Span::from(format!("  {:<14}", "")),
+                    Span::from("Select a lift by number, as in 1 2 for 12"),
+                ]));
+                lines.push(Line::from(vec![
+                    Span::from(format!("{:>10}", "click")).bold(),
+                    Span::from(format!("  {:<14}", "")),
+                    Span::from("Pick a landing or select a lift"),
+                ]));
+                let area = centered_rect(76, lines.len() as u16 + 2, frame.size());
+                // Scroll what does not fit, no further than the last line.
+                let hidden = (lines.len() as u16).saturating_sub(area.height.saturating_sub(2));
+                ui.help_scroll = ui.help_scroll.min(hidden);
+                let title = if hidden > 0 {
+                    "Keys (up/down to scroll, ? or esc to close)"
+                } else {
+                    "Keys (? or esc to close)"
+                };
+                frame.render_widget(Clear, area);
+                frame.render_widget(
+                    Paragraph::new(lines)
+                        .scroll((ui.help_scroll, 0))
+                        .block(Block::default().title(title).borders(Borders::ALL)),
+                    area,
+                );
+            }
         })?;
         if event::poll(std::time::Duration::from_millis(16))? {
+            let event = event::read()?;
+            if let event::Event::Resize(..) = event {
+                // Lay everything out again at the new size, which may switch
+                // compact mode on or off.
+                terminal.autoresize()?;
+                continue;
+            }
             if ui.popup_active() {
-                match event::read()?.into() {
+                match event.into() {
                     Input { key: Key::Esc, .. } => {
                         ui.reset();
                     }
+                    Input {
+                        key: Key::Enter, ..
+                    } if is_valid && ui.prompt().is_some() => {
+                        ui.run_command(ui.prompt().unwrap(), &building);
+                        ui.reset();
+                    }
                     Input {
                         key: Key::Enter, ..
                     } if is_valid => {
@@ -301,6 +986,16 @@ fn main() -> Result<()> {
                     Input {
                         key: Key::Enter, ..
                     } => {}
+                    Input {
+                        key: key @ (Key::Up | Key::Down),
+                        ..
+                    } if ui.prompt() == Some(Prompt::CommandLine) => {
+                        ui.browse(key == Key::Up);
+                        is_valid = ui.validate(&building);
+                    }
+                    Input { key: Key::Tab, .. } if ui.prompt() == Some(Prompt::CommandLine) => {
+                        is_valid = ui.complete(&building);
+                    }
                     input => {
                         if ui.textarea.input(input) {
                             is_valid = ui.validate(&building);
@@ -308,16 +1003,99 @@ fn main() -> Result<()> {
                     }
                 }
             } else {
-                if let event::Event::Key(key) = event::read()? {
+                if let event::Event::Mouse(mouse) = event {
+                    if mouse.kind == MouseEventKind::Down(MouseButton::Left) {
+                        ui.click(&building, mouse.column, mouse.row);
+                    }
+                }
+                if let event::Event::Key(key) = event {
                     if key.kind == KeyEventKind::Press {
-                        match key.code {
-                            KeyCode::Esc => break,
-                            KeyCode::Char('q') => break,
-                            KeyCode::Char(' ') => ui.next_state(),
-                            KeyCode::Char('d') => building.debug(),
-                            KeyCode::Char('r') => building.random(),
-                            KeyCode::Char('R') => building.realistic_random(),
-                            _ => {}
+                        if ui.help {
+                            match key.code {
+                                KeyCode::Up => ui.help_scroll = ui.help_scroll.saturating_sub(1),
+                                KeyCode::Down => ui.help_scroll += 1,
+                                KeyCode::Esc => ui.help = false,
+                                code if ui.keys.action(code) == Some(Action::Help) => {
+                                    ui.help = false
+                                }
+                                _ => {}
+                            }
+                            continue;
+                        }
+                        match (ui.keys.action(key.code), key.code) {
+                            (Some(Action::Quit), _) => break,
+                            (Some(Action::Help), _) => {
+                                ui.help = true;
+                                ui.help_scroll = 0;
+                            }
+                            (Some(Action::NewPassenger), _) => ui.next_state(),
+                            (Some(Action::Fault), _) => {
+                                ui.open_prompt(Prompt::Fault);
+                                is_valid = false;
+                            }
+                            (Some(Action::CarCall), _) => {
+                                ui.open_prompt(Prompt::CarCall);
+                                is_valid = false;
+                            }
+                            (Some(Action::Call), _) => {
+                                ui.open_prompt(Prompt::Call);
+                                is_valid = false;
+                            }
+                            (Some(Action::CommandLine), _) => {
+                                ui.open_prompt(Prompt::CommandLine);
+                                is_valid = false;
+                            }
+                            (Some(Action::PreviousLift), _) => {
+                                let count = building.lift_count().max(1);
+                                ui.select((ui.selected + count - 1) % count);
+                            }
+                            (Some(Action::NextLift), _) => {
+                                ui.select((ui.selected + 1) % building.lift_count().max(1));
+                            }
+                            (Some(Action::ScrollUp), _) => ui.scroll(&building, true),
+                            (Some(Action::ScrollDown), _) => ui.scroll(&building, false),
+                            (Some(Action::Independent), _) => {
+                                if let Ok(independent) = building.independent(ui.selected) {
+                                    let _ = building.set_independent(ui.selected, !independent);
+                                }
+                            }
+                            (Some(Action::FireRecall), _) => {
+                                let _ = match building.recalled() {
+                                    Ok(true) => building.end_recall(),
+                                    _ => building.recall(None),
+                                };
+                            }
+                            (Some(Action::Pause), _) => clock::set_paused(!clock::paused()),
+                            (Some(Action::Step), _) => clock::step(),
+                            (Some(Action::Faster), _) => {
+                                clock::set_speed(clock::next_speed(clock::speed(), true))
+                            }
+                            (Some(Action::Slower), _) => {
+                                clock::set_speed(clock::next_speed(clock::speed(), false))
+                            }
+                            (Some(Action::Debug), _) => building.debug(),
+                            (Some(Action::LogFilter), _) => {
+                                ui.log_filtered = !ui.log_filtered;
+                                ui.log_scroll = 0;
+                            }
+                            (Some(Action::LogOlder), _) => ui.log_scroll += LOG_HEIGHT as usize - 2,
+                            (Some(Action::LogNewer), _) => {
+                                ui.log_scroll =
+                                    ui.log_scroll.saturating_sub(LOG_HEIGHT as usize - 2)
+                            }
+                            (Some(Action::View), _) => ui.view = ui.view.next(),
+                            (Some(Action::Random), _) => building.random(),
+                            (Some(Action::RandomLobby), _) => building.realistic_random(),
+                            (Some(Action::Mode), _) => {
+                                if let Ok((mode, auto_mode)) = building.mode() {
+                                    let _ = building.set_mode(next_mode(mode, auto_mode));
+                                }
+                            }
+                            // Digits not bound to anything else select a lift.
+                            (None, KeyCode::Char(c @ '0'..='9')) => {
+                                ui.type_digit(c.to_digit(10).unwrap(), building.lift_count());
+                            }
+                            (None, _) => {}
                         }
                     }
                 }
@@ -325,19 +1103,170 @@ fn main() -> Result<()> {
         }
     }
 
+    stdout().execute(DisableMouseCapture)?;
     stdout().execute(LeaveAlternateScreen)?;
     disable_raw_mode()?;
 
+    print_energy_report(&building);
+    print_class_report(&building);
+
     Ok(())
 }
 
-fn bar_width(rect: &Rect, bars: u16) -> u16 {
-    let mut total_width = rect.width;
-    total_width -= 2;
-    let bar_width = (total_width / bars) - 1;
-    if bar_width > 4 {
-        bar_width
+fn chart_title(building: &Building) -> String {
+    let kwh: f64 = building
+        .energy_report()
+        .map(|r| r.iter().map(|(_, m)| m.kwh()).sum())
+        .unwrap_or(0.0);
+    let mode = match (building.recalled(), building.firefighter_car()) {
+        (Ok(true), Ok(Some(id))) => format!("FIRE RECALL, lift {} on firefighter service", id),
+        (Ok(true), _) => "FIRE RECALL".to_string(),
+        _ => match building.mode() {
+            Ok((mode, true)) => format!("{} (auto)", mode),
+            Ok((mode, false)) => mode.to_string(),
+            Err(_) => "unknown".to_string(),
+        },
+    };
+    match building.journey_summary() {
+        Ok((count, transfers, average)) if count > 0 => format!(
+            "Lifts: {} ({} journeys, {} with transfers, average {:.1}s, {:.3} kWh)",
+            mode,
+            count,
+            transfers,
+            average.as_secs_f64(),
+            kwh
+        ),
+        _ => format!("Lifts: {} ({:.3} kWh)", mode, kwh),
+    }
+}
+
+fn speed_title() -> String {
+    if clock::paused() {
+        "PAUSED".to_string()
     } else {
-        4
+        format!("{}x speed", clock::speed())
+    }
+}
+
+fn log_title(lift: Option<u32>, scroll: usize) -> String {
+    let title = match lift {
+        Some(id) => format!("Events for lift {}", id),
+        None => "Events".to_string(),
+    };
+    if scroll > 0 {
+        format!("{}, scrolled back {}", title, scroll)
+    } else {
+        title
+    }
+}
+
+fn traffic_title(building: &Building) -> String {
+    let traffic = match building.traffic() {
+        Ok(traffic) => format!(
+            "Traffic: {} ({} in, {} out, {} interfloor, {:.1} calls/min)",
+            traffic.pattern, traffic.incoming, traffic.outgoing, traffic.interfloor, traffic.rate
+        ),
+        Err(_) => "Traffic: unknown".to_string(),
+    };
+    match building.abandonment() {
+        Ok((gave_up, walked, rate)) if gave_up + walked > 0 => format!(
+            "{} ({} gave up, {:.1}%, {} took the stairs)",
+            traffic,
+            gave_up,
+            rate * 100.0,
+            walked
+        ),
+        _ => traffic,
+    }
+}
+
+/// The mode after `mode` in the cycle automatic, balanced, up-peak,
+/// down-peak, where `None` is automatic.
+fn next_mode(mode: Mode, auto_mode: bool) -> Option<Mode> {
+    match (auto_mode, mode) {
+        (true, _) => Some(Mode::Balanced),
+        (false, Mode::Balanced) => Some(Mode::UpPeak),
+        (false, Mode::UpPeak) => Some(Mode::DownPeak),
+        (false, Mode::DownPeak) => None,
+    }
+}
+
+fn print_energy_report(building: &Building) {
+    let Ok(report) = building.energy_report() else {
+        return;
+    };
+    let mut total = 0.0;
+    for (id, meter) in &report {
+        println!(
+            "Lift {}: {:.4} kWh ({:.4} motor, {:.4} regenerated, {:.4} standby), {} trips, {} floors",
+            id,
+            meter.kwh(),
+            kwh(meter.motor),
+            kwh(meter.regenerated),
+            kwh(meter.standby),
+            meter.trips,
+            meter.floors
+        );
+        total += meter.kwh();
+    }
+    println!("Total: {:.4} kWh", total);
+}
+
+fn print_class_report(building: &Building) {
+    let Ok(classes) = building.class_summary() else {
+        return;
+    };
+    for (class, count, average, longest) in classes {
+        println!(
+            "Journeys ({}): {}, average {:.1}s, longest {:.1}s",
+            class,
+            count,
+            average.as_secs_f64(),
+            longest.as_secs_f64()
+        );
+    }
+    if let Ok((gave_up, walked, rate)) = building.abandonment() {
+        println!(
+            "Abandoned: {} gave up waiting ({:.1}%), {} took the stairs",
+            gave_up,
+            rate * 100.0,
+            walked
+        );
+    }
+}
+
+/// The width of each bar, and how many of `bars` fit in `rect`, with a gap
+/// after every bar but the last.
+fn bar_layout(rect: &Rect, bars: usize) -> (u16, usize) {
+    let total_width = rect.width.saturating_sub(2) as usize + 1;
+    let fit = (total_width / (MIN_BAR_WIDTH + 1)).clamp(1, bars.max(1));
+    let width = (total_width / fit).saturating_sub(1).max(1);
+    (width as u16, fit)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn tiny_terminals() {
+        for (width, height) in [(0, 0), (1, 1), (2, 2), (80, 2), (3, 40)] {
+            let r = Rect::new(0, 0, width, height);
+            let popup = centered_line(60, r);
+            assert!(popup.width <= width && popup.height <= height.min(3));
+            assert!(popup.right() <= r.right() && popup.bottom() <= r.bottom());
+            let help = centered_rect(76, 30, r);
+            assert_eq!((help.width, help.height), (width.min(76), height.min(30)));
+            for bars in [0, 1, 40] {
+                let (bar_width, fit) = bar_layout(&r, bars);
+                assert!(bar_width >= 1 && (1..=bars.max(1)).contains(&fit));
+            }
+        }
+        assert_eq!(
+            centered_line(60, Rect::new(0, 0, 100, 24)),
+            Rect::new(20, 10, 60, 3)
+        );
+        assert_eq!(bar_layout(&Rect::new(0, 0, 52, 10), 40), (4, 10));
+        assert_eq!(bar_layout(&Rect::new(0, 0, 52, 10), 2), (24, 2));
     }
 }
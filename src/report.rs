@@ -0,0 +1,121 @@
+use crate::building::Building;
+use std::fs;
+
+/// A summary of the run so far as JSON: the operating mode, journeys in
+/// total and by class, abandonment, and each lift's energy and travel.
+pub fn json(building: &Building) -> Result<String, String> {
+    let (mode, auto_mode) = building.mode()?;
+    let (count, transfers, average) = building.journey_summary()?;
+    let classes = building
+        .class_summary()?
+        .iter()
+        .map(|(class, count, average, longest)| {
+            format!(
+                r#"    {{"class": {}, "count": {}, "average_seconds": {:.3}, "longest_seconds": {:.3}}}"#,
+                json_string(&class.to_string()),
+                count,
+                average.as_secs_f64(),
+                longest.as_secs_f64()
+            )
+        })
+        .collect::<Vec<_>>();
+    let (gave_up, walked, rate) = building.abandonment()?;
+    let report = building.energy_report()?;
+    let lifts = report
+        .iter()
+        .map(|(id, meter)| {
+            format!(
+                r#"    {{"id": {}, "kwh": {:.6}, "trips": {}, "floors": {}}}"#,
+                id,
+                meter.kwh(),
+                meter.trips,
+                meter.floors
+            )
+        })
+        .collect::<Vec<_>>();
+    let total: f64 = report.iter().map(|(_, m)| m.kwh()).sum();
+    Ok(format!(
+        r#"{{
+  "mode": {},
+  "auto_mode": {},
+  "journeys": {},
+  "transfers": {},
+  "average_seconds": {:.3},
+  "classes": [
+{}
+  ],
+  "gave_up": {},
+  "walked": {},
+  "abandonment_rate": {:.4},
+  "lifts": [
+{}
+  ],
+  "total_kwh": {:.6}
+}}
+"#,
+        json_string(&mode.to_string()),
+        auto_mode,
+        count,
+        transfers,
+        average.as_secs_f64(),
+        classes.join(",\n"),
+        gave_up,
+        walked,
+        rate,
+        lifts.join(",\n"),
+        total
+    ))
+}
+
+pub fn save(building: &Building, path: &str) -> Result<(), String> {
+    fs::write(path, json(building)?).map_err(|e| format!("Failed to write {}: {}", path, e))
+}
+
+/// A string as a quoted JSON string, with quotes, backslashes and control
+/// characters escaped.
+fn json_string(s: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::building::{BankSpec, Building};
+    use crate::energy::EnergyModel;
+    use crate::report::*;
+
+    #[test]
+    fn escape_strings() {
+        assert_eq!(json_string("up-peak"), r#""up-peak""#);
+        assert_eq!(
+            json_string("say \"hi\"\\\n\u{1}"),
+            r#""say \"hi\"\\\n\u0001""#
+        );
+    }
+
+    #[test]
+    fn report_shape() {
+        let specs = BankSpec::zoned(0, 10, 2, 1, &[]);
+        let (building, _arrivals) = Building::unstarted(0, 10, &specs, EnergyModel::default());
+        let json = json(&building).unwrap();
+        assert!(json.starts_with("{\n") && json.ends_with("}\n"));
+        assert!(json.contains(r#""mode": "balanced","#));
+        assert!(json.contains("\"classes\": [\n\n  ],"));
+        assert_eq!(json.matches(r#"{"id": "#).count(), 2);
+        for (open, close) in [('{', '}'), ('[', ']')] {
+            assert_eq!(json.matches(open).count(), json.matches(close).count());
+        }
+    }
+}
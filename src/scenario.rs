@@ -0,0 +1,453 @@
+use crate::building::{Building, Dispatch, Fault, Passenger, Priority};
+use crate::clock::{self, Instant};
+use crate::report;
+use clap::ValueEnum;
+use std::{fs, str::FromStr, sync::Arc, thread, time::Duration};
+
+/// Every command's first word, for completion.
+const COMMANDS: [&str; 13] = [
+    "call",
+    "cancel",
+    "car",
+    "fail",
+    "fault",
+    "independent",
+    "recall",
+    "repair",
+    "save",
+    "seed",
+    "speed",
+    "strategy",
+    "wrong-car",
+];
+
+const FAULTS: [&str; 3] = ["out", "breakdown", "doors"];
+
+const CLASSES: [&str; 4] = ["normal", "accessibility", "vip", "emergency"];
+
+const STRATEGIES: [&str; 3] = ["wait", "energy", "etd"];
+
+/// Something that can be done to the building, from a scenario file or the
+/// TUI.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// `call FROM TO [CLASS] [xGROUP]`: a passenger, or a group of people
+    /// travelling together, calls a lift.
+    Call {
+        from_floor: i32,
+        to_floor: i32,
+        priority: Priority,
+        group: usize,
+    },
+    /// `fault LIFT out|breakdown|doors`: a lift develops a fault. `fail
+    /// [lift] LIFT [FAULT]` does the same, breaking the lift down unless
+    /// told otherwise.
+    Fault { lift: u32, fault: Fault },
+    /// `repair LIFT`: a lift is returned to service.
+    Repair { lift: u32 },
+    /// `recall [FLOOR]`: fire recall, to the given floor or each bank's
+    /// lobby.
+    Recall { floor: Option<i32> },
+    /// `recall off`: the fire recall ends.
+    EndRecall,
+    /// `car LIFT FLOOR`: a car call made from inside a lift.
+    CarCall { lift: u32, floor: i32 },
+    /// `cancel call FROM TO`: someone who called a lift walks away.
+    CancelCall { from_floor: i32, to_floor: i32 },
+    /// `cancel car LIFT FLOOR`: a car call is cancelled.
+    CancelCarCall { lift: u32, floor: i32 },
+    /// `wrong-car FROM TO`: someone waiting gets into the wrong lift.
+    WrongCar { from_floor: i32, to_floor: i32 },
+    /// `independent LIFT [off]`: a lift is put on independent service, or
+    /// given back to group control.
+    Independent { lift: u32, independent: bool },
+    /// `speed MULTIPLE`: the simulation runs at this multiple of real time,
+    /// from 0.25 to 100.
+    Speed { speed: f64 },
+    /// `strategy wait|energy|etd`: what the dispatchers minimise.
+    Strategy { dispatch: Dispatch },
+    /// `seed NUMBER`: random passengers are drawn from this seed from now
+    /// on.
+    Seed { seed: u64 },
+    /// `save PATH`: a summary of the run is written to a JSON file.
+    Save { path: String },
+}
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        let number = |i: usize| -> Result<i32, String> {
+            let word = words.get(i).ok_or("Missing number".to_string())?;
+            word.parse::<i32>()
+                .map_err(|e| format!("Invalid number '{}': {}", word, e))
+        };
+        let lift = |i: usize| -> Result<u32, String> {
+            let word = words.get(i).ok_or("Missing lift".to_string())?;
+            word.parse::<u32>()
+                .map_err(|e| format!("Invalid lift '{}': {}", word, e))
+        };
+        match words.first() {
+            Some(&"call") if (3..=5).contains(&words.len()) => {
+                let mut priority = Priority::Normal;
+                let mut group = 1;
+                for word in &words[3..] {
+                    match word.strip_prefix('x') {
+                        Some(size) => {
+                            group = size
+                                .parse::<usize>()
+                                .ok()
+                                .filter(|g| *g > 0)
+                                .ok_or(format!("Invalid group size '{}'", size))?;
+                        }
+                        None => priority = word.parse()?,
+                    }
+                }
+                Ok(Command::Call {
+                    from_floor: number(1)?,
+                    to_floor: number(2)?,
+                    priority,
+                    group,
+                })
+            }
+            Some(&"fault") if words.len() == 3 => Ok(Command::Fault {
+                lift: lift(1)?,
+                fault: words[2].parse()?,
+            }),
+            Some(&"fail") if (2..=4).contains(&words.len()) => {
+                let at = if words[1] == "lift" { 2 } else { 1 };
+                let fault = match words.get(at + 1) {
+                    Some(word) => word.parse()?,
+                    None => Fault::Breakdown,
+                };
+                match words.len() - at {
+                    1 | 2 => Ok(Command::Fault {
+                        lift: lift(at)?,
+                        fault,
+                    }),
+                    _ => Err("Expected a lift and a fault".to_string()),
+                }
+            }
+            Some(&"speed") if words.len() == 2 => {
+                let speed = words[1]
+                    .trim_end_matches('x')
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|s| (0.25..=100.0).contains(s))
+                    .ok_or(format!(
+                        "Invalid speed '{}': expected 0.25 to 100",
+                        words[1]
+                    ))?;
+                Ok(Command::Speed { speed })
+            }
+            Some(&"strategy") if words.len() == 2 => Ok(Command::Strategy {
+                dispatch: <Dispatch as ValueEnum>::from_str(words[1], true).map_err(|_| {
+                    format!(
+                        "Invalid strategy '{}': expected one of {}",
+                        words[1],
+                        STRATEGIES.join(", ")
+                    )
+                })?,
+            }),
+            Some(&"seed") if words.len() == 2 => Ok(Command::Seed {
+                seed: words[1]
+                    .parse()
+                    .map_err(|e| format!("Invalid seed '{}': {}", words[1], e))?,
+            }),
+            Some(&"save") if words.len() == 2 => Ok(Command::Save {
+                path: words[1].to_string(),
+            }),
+            Some(&"repair") if words.len() == 2 => Ok(Command::Repair { lift: lift(1)? }),
+            Some(&"recall") if words.get(1) == Some(&"off") => Ok(Command::EndRecall),
+            Some(&"recall") if words.len() == 1 => Ok(Command::Recall { floor: None }),
+            Some(&"recall") if words.len() == 2 => Ok(Command::Recall {
+                floor: Some(number(1)?),
+            }),
+            Some(&"independent") if words.len() == 2 => Ok(Command::Independent {
+                lift: lift(1)?,
+                independent: true,
+            }),
+            Some(&"independent") if words.get(2) == Some(&"off") => Ok(Command::Independent {
+                lift: lift(1)?,
+                independent: false,
+            }),
+            Some(&"cancel") if words.len() == 4 && words[1] == "call" => Ok(Command::CancelCall {
+                from_floor: number(2)?,
+                to_floor: number(3)?,
+            }),
+            Some(&"cancel") if words.len() == 4 && words[1] == "car" => {
+                Ok(Command::CancelCarCall {
+                    lift: lift(2)?,
+                    floor: number(3)?,
+                })
+            }
+            Some(&"wrong-car") if words.len() == 3 => Ok(Command::WrongCar {
+                from_floor: number(1)?,
+                to_floor: number(2)?,
+            }),
+            Some(&"car") if words.len() == 3 => Ok(Command::CarCall {
+                lift: lift(1)?,
+                floor: number(2)?,
+            }),
+            Some(word) => Err(format!("Invalid command '{}'", word)),
+            None => Err("Empty command".to_string()),
+        }
+    }
+}
+
+impl Command {
+    pub fn apply(&self, building: &Building) -> Result<(), String> {
+        match *self {
+            Command::Speed { speed } => {
+                clock::set_speed(speed);
+                Ok(())
+            }
+            Command::Strategy { dispatch } => building.set_dispatch(dispatch),
+            Command::Seed { seed } => building.set_seed(seed),
+            Command::Save { ref path } => report::save(building, path),
+            Command::Call {
+                from_floor,
+                to_floor,
+                priority,
+                group,
+            } => building
+                .respond(
+                    Passenger::new(from_floor, to_floor)
+                        .with_priority(priority)
+                        .with_group(group),
+                )
+                .map(|_| ()),
+            Command::Fault { lift, fault } => building.fail(lift, fault),
+            Command::Repair { lift } => building.repair(lift),
+            Command::Recall { floor } => building.recall(floor),
+            Command::EndRecall => building.end_recall(),
+            Command::CarCall { lift, floor } => building.car_call(lift, floor),
+            Command::CancelCall {
+                from_floor,
+                to_floor,
+            } => building.cancel_call(from_floor, to_floor),
+            Command::CancelCarCall { lift, floor } => building.cancel_car_call(lift, floor),
+            Command::WrongCar {
+                from_floor,
+                to_floor,
+            } => building.wrong_car(from_floor, to_floor).map(|_| ()),
+            Command::Independent { lift, independent } => {
+                building.set_independent(lift, independent)
+            }
+        }
+    }
+}
+
+/// The words that could finish the last, partly typed word of a command.
+pub fn completions(input: &str) -> Vec<&'static str> {
+    let mut words: Vec<&str> = input.split_whitespace().collect();
+    let partial = match input.ends_with(char::is_whitespace) {
+        true => "",
+        false => words.pop().unwrap_or(""),
+    };
+    let candidates: &[&str] = match words[..] {
+        [] => &COMMANDS,
+        ["cancel"] => &["call", "car"],
+        ["recall"] => &["off"],
+        ["strategy"] => &STRATEGIES,
+        ["fail"] => &["lift"],
+        ["fault", _] | ["fail", "lift", _] => &FAULTS,
+        ["fail", lift] if lift != "lift" => &FAULTS,
+        ["call", _, _] | ["call", _, _, _] => &CLASSES,
+        ["independent", _] => &["off"],
+        _ => &[],
+    };
+    candidates
+        .iter()
+        .copied()
+        .filter(|c| c.starts_with(partial))
+        .collect()
+}
+
+/// A list of commands to run at set times after the simulation starts. Each
+/// line of a scenario file holds the time in seconds and then a command, as
+/// in `12.5 fault 2 breakdown`. Blank lines and lines starting with `#` are
+/// ignored.
+#[derive(Debug, Default)]
+pub struct Scenario {
+    events: Vec<(Duration, Command)>,
+}
+
+impl FromStr for Scenario {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut events = Vec::new();
+        for (number, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |e: String| format!("Line {}: {}", number + 1, e);
+            let (time, command) = line
+                .split_once(char::is_whitespace)
+                .ok_or(error("Expected a time and a command".to_string()))?;
+            let time = time
+                .parse::<f64>()
+                .ok()
+                .filter(|t| *t >= 0.0)
+                .ok_or(error(format!("Invalid time '{}'", time)))?;
+            events.push((
+                Duration::from_secs_f64(time),
+                command.parse().map_err(error)?,
+            ));
+        }
+        events.sort_by_key(|(time, _)| *time);
+        Ok(Scenario { events })
+    }
+}
+
+impl Scenario {
+    pub fn load(path: &str) -> Result<Scenario, String> {
+        fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path, e))?
+            .parse()
+            .map_err(|e| format!("{}: {}", path, e))
+    }
+
+    /// Run the scenario's commands against the building as their times come
+    /// round, logging any that fail.
+    pub fn start(self, building: Arc<Building>) {
+        let started = Instant::now();
+        thread::spawn(move || {
+            for (time, command) in self.events {
+                if let Some(wait) = time.checked_sub(started.elapsed()) {
+                    clock::sleep(wait);
+                }
+                if let Err(e) = command.apply(&building) {
+                    let message = format!("Scenario at {:.1}s: {}", time.as_secs_f64(), e);
+                    let _ = building.log(None, message);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scenario::*;
+
+    #[test]
+    fn parse_scenario() {
+        let scenario: Scenario = "# Lift 1 breaks down with passengers aboard\n\
+                                  0 call 0 7\n\
+                                  \n\
+                                  10 repair 1\n\
+                                  2.5 fault 1 breakdown\n"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            scenario.events,
+            vec![
+                (
+                    Duration::ZERO,
+                    Command::Call {
+                        from_floor: 0,
+                        to_floor: 7,
+                        priority: Priority::Normal,
+                        group: 1
+                    }
+                ),
+                (
+                    Duration::from_millis(2500),
+                    Command::Fault {
+                        lift: 1,
+                        fault: Fault::Breakdown
+                    }
+                ),
+                (Duration::from_secs(10), Command::Repair { lift: 1 }),
+            ]
+        );
+        assert!("1 fault 1 melted".parse::<Scenario>().is_err());
+        assert!("soon call 0 7".parse::<Scenario>().is_err());
+        assert!("1 call 0".parse::<Scenario>().is_err());
+        assert_eq!(
+            "recall".parse::<Command>(),
+            Ok(Command::Recall { floor: None })
+        );
+        assert_eq!("recall off".parse::<Command>(), Ok(Command::EndRecall));
+        assert_eq!(
+            "call 3 0 accessibility".parse::<Command>(),
+            Ok(Command::Call {
+                from_floor: 3,
+                to_floor: 0,
+                priority: Priority::Accessibility,
+                group: 1
+            })
+        );
+        assert_eq!(
+            "call 0 5 x10".parse::<Command>(),
+            Ok(Command::Call {
+                from_floor: 0,
+                to_floor: 5,
+                priority: Priority::Normal,
+                group: 10
+            })
+        );
+        assert!("call 0 5 x0".parse::<Command>().is_err());
+        assert_eq!(
+            "cancel car 1 9".parse::<Command>(),
+            Ok(Command::CancelCarCall { lift: 1, floor: 9 })
+        );
+        assert!("cancel 1 9".parse::<Command>().is_err());
+        assert!("call 3 0 urgent".parse::<Command>().is_err());
+        assert_eq!(
+            "independent 3 off".parse::<Command>(),
+            Ok(Command::Independent {
+                lift: 3,
+                independent: false
+            })
+        );
+        assert_eq!(
+            "car 2 -1".parse::<Command>(),
+            Ok(Command::CarCall { lift: 2, floor: -1 })
+        );
+        assert_eq!(
+            "fail lift 2".parse::<Command>(),
+            Ok(Command::Fault {
+                lift: 2,
+                fault: Fault::Breakdown
+            })
+        );
+        assert_eq!(
+            "fail 2 doors".parse::<Command>(),
+            Ok(Command::Fault {
+                lift: 2,
+                fault: Fault::Doors
+            })
+        );
+        assert_eq!(
+            "speed 10".parse::<Command>(),
+            Ok(Command::Speed { speed: 10.0 })
+        );
+        assert!("speed 1000".parse::<Command>().is_err());
+        assert_eq!(
+            "strategy energy".parse::<Command>(),
+            Ok(Command::Strategy {
+                dispatch: Dispatch::Energy
+            })
+        );
+        assert_eq!(
+            "strategy etd".parse::<Command>(),
+            Ok(Command::Strategy {
+                dispatch: Dispatch::Etd
+            })
+        );
+        assert!("strategy fastest".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn complete_commands() {
+        assert_eq!(completions("s"), ["save", "seed", "speed", "strategy"]);
+        assert_eq!(completions("cancel c"), ["call", "car"]);
+        assert_eq!(completions("fail lift 2 b"), ["breakdown"]);
+        assert_eq!(completions("call 0 5 "), CLASSES);
+        assert!(completions("call 0 ").is_empty());
+    }
+}
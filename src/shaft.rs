@@ -0,0 +1,309 @@
+use crate::building::{Bank, Car};
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Style, Stylize},
+    widgets::Widget,
+};
+use std::ops::Range;
+
+/// Width of a shaft, including its left wall.
+const SHAFT_WIDTH: u16 = 4;
+
+/// Width of a shaft in compact mode, where the car is just its symbol.
+const COMPACT_SHAFT_WIDTH: u16 = 2;
+
+/// Width of the landing column showing how many people are waiting.
+const LANDING_WIDTH: u16 = 5;
+
+/// What is drawn at a point in the view.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hit {
+    /// A floor's label or landing.
+    Landing(i32),
+    /// Anywhere in a lift's shaft.
+    Lift(u32),
+}
+
+/// An elevation of one bank: a row per floor from the top down, with the
+/// people waiting at each landing and a column per shaft with the car drawn
+/// at its floor.
+#[derive(Debug)]
+pub struct ShaftView {
+    lowest: i32,
+    highest: i32,
+    lobby: i32,
+    cars: Vec<Car>,
+    waiting: Vec<(i32, usize)>,
+    selected: u32,
+    /// A floor picked as the start of a call, waiting for a destination.
+    origin: Option<i32>,
+    /// Floors the view is moved up from the page it would show.
+    scroll: i32,
+    compact: bool,
+}
+
+/// The highest floor to draw when `rows` of the floors from `lowest` to
+/// `highest` fit. Floors are shown a page of `rows` at a time, counting
+/// down from `highest`, and the page is the one with `follow` on it, moved
+/// up by `scroll` floors and kept within the building.
+pub fn top_floor(lowest: i32, highest: i32, rows: u16, follow: i32, scroll: i32) -> i32 {
+    let rows = rows.max(1) as i32;
+    if highest - lowest < rows {
+        return highest;
+    }
+    let page = (highest - follow.clamp(lowest, highest)) / rows;
+    (highest - page * rows + scroll).clamp(lowest + rows - 1, highest)
+}
+
+/// The first of `count` lifts to draw when `fit` of them fit: the start of
+/// the page of lifts with the selected one on it, if any.
+pub fn first_lift(selected: Option<usize>, fit: usize, count: usize) -> usize {
+    let fit = fit.max(1);
+    (selected.unwrap_or(0) / fit * fit).min(count.saturating_sub(fit))
+}
+
+impl ShaftView {
+    pub fn new(bank: &Bank, selected: u32, origin: Option<i32>) -> Result<ShaftView, String> {
+        let (lowest, highest) = bank.floors();
+        Ok(ShaftView {
+            lowest,
+            highest,
+            lobby: bank.lobby,
+            cars: bank.cars()?,
+            waiting: bank
+                .landings()?
+                .into_iter()
+                .map(|(floor, people, _)| (floor, people))
+                .collect(),
+            selected,
+            origin,
+            scroll: 0,
+            compact: false,
+        })
+    }
+
+    /// Move the view up by `floors`, or down if negative.
+    pub fn scroll(mut self, floors: i32) -> ShaftView {
+        self.scroll = floors;
+        self
+    }
+
+    /// Draw narrow shafts, for small terminals.
+    pub fn compact(mut self, compact: bool) -> ShaftView {
+        self.compact = compact;
+        self
+    }
+
+    fn label_width(&self) -> u16 {
+        self.lowest
+            .to_string()
+            .len()
+            .max(self.highest.to_string().len()) as u16
+    }
+
+    fn shaft_width(&self) -> u16 {
+        if self.compact {
+            COMPACT_SHAFT_WIDTH
+        } else {
+            SHAFT_WIDTH
+        }
+    }
+
+    /// The highest floor and the cars that fit in `area`. A bank too tall
+    /// or too wide is shown a page at a time, turned to the selected car.
+    fn window(&self, area: Rect) -> (i32, Range<usize>) {
+        let selected = self.cars.iter().position(|car| car.id == self.selected);
+        let follow = selected.map_or(self.lobby, |i| self.cars[i].floor);
+        let top = top_floor(self.lowest, self.highest, area.height, follow, self.scroll);
+        // Leave room for the last shaft's right wall.
+        let landing = self.label_width() + 1 + LANDING_WIDTH;
+        let fit = (area.width.saturating_sub(landing + 1) / self.shaft_width()) as usize;
+        let first = first_lift(selected, fit, self.cars.len());
+        (top, first..(first + fit.max(1)).min(self.cars.len()))
+    }
+
+    /// Which floors and lifts are in view, if they don't all fit in `area`.
+    pub fn scrolled(&self, area: Rect) -> Option<String> {
+        let (top, cars) = self.window(area);
+        let bottom = (top - area.height as i32 + 1).max(self.lowest);
+        let mut parts = Vec::new();
+        if bottom > self.lowest || top < self.highest {
+            parts.push(format!("floors {} to {}", bottom, top));
+        }
+        if cars.len() < self.cars.len() && !cars.is_empty() {
+            parts.push(format!(
+                "lifts {} to {} of {}",
+                self.cars[cars.start].id,
+                self.cars[cars.end - 1].id,
+                self.cars.len()
+            ));
+        }
+        (!parts.is_empty()).then(|| parts.join(", "))
+    }
+
+    /// What the view, drawn in `area`, shows at a point on the screen.
+    pub fn hit(&self, area: Rect, x: u16, y: u16) -> Option<Hit> {
+        if x < area.left() || x >= area.right() || y < area.top() || y >= area.bottom() {
+            return None;
+        }
+        let (top, cars) = self.window(area);
+        let floor = top - (y - area.top()) as i32;
+        if floor < self.lowest {
+            return None;
+        }
+        let landing = self.label_width() + 1 + LANDING_WIDTH;
+        let column = x - area.left();
+        if column < landing {
+            return Some(Hit::Landing(floor));
+        }
+        let shaft = cars.start + ((column - landing) / self.shaft_width()) as usize;
+        cars.contains(&shaft)
+            .then(|| Hit::Lift(self.cars[shaft].id))
+    }
+
+    fn car_style(&self, car: &Car) -> Style {
+        if car.fault.is_some() {
+            Style::new().white().on_red()
+        } else if car.id == self.selected {
+            Style::new().black().on_yellow()
+        } else if car.doors_open {
+            Style::new().black().on_cyan()
+        } else {
+            Style::new().black().on_green()
+        }
+    }
+}
+
+impl Widget for ShaftView {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let label_width = self.label_width();
+        let (top, cars) = self.window(area);
+        let floors = (self.lowest..=top).rev();
+        for (y, floor) in (area.top()..area.bottom()).zip(floors) {
+            let mut x = area.left();
+            let mut put = |x: u16, text: &str, style: Style| {
+                if x < area.right() {
+                    buf.set_stringn(x, y, text, (area.right() - x) as usize, style);
+                }
+            };
+            let label_style = if Some(floor) == self.origin {
+                Style::new().black().on_yellow()
+            } else if floor == self.lobby {
+                Style::new().bold()
+            } else {
+                Style::new()
+            };
+            put(
+                x,
+                &format!("{:>1$}", floor, label_width as usize),
+                label_style,
+            );
+            x += label_width + 1;
+            if let Ok(pos) = self.waiting.binary_search_by_key(&floor, |(f, _)| *f) {
+                let count = self.waiting[pos].1.min(999);
+                put(x, &format!("☺{}", count), Style::new().yellow());
+            }
+            x += LANDING_WIDTH;
+            for car in &self.cars[cars.clone()] {
+                put(x, "│", Style::new().dark_gray());
+                if car.floor <= floor && floor < car.floor + car.decks {
+                    let text = if self.compact {
+                        car.symbol.to_string()
+                    } else {
+                        format!("[{}]", car.symbol)
+                    };
+                    put(x + 1, &text, self.car_style(car));
+                }
+                x += self.shaft_width();
+            }
+            put(x, "│", Style::new().dark_gray());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::building::Car;
+    use crate::shaft::*;
+
+    #[test]
+    fn draws_and_hits_basements() {
+        let car = |id, floor, decks| Car {
+            id,
+            floor,
+            decks,
+            doors_open: false,
+            fault: None,
+            symbol: '↑',
+        };
+        let view = || ShaftView {
+            lowest: -2,
+            highest: 1,
+            lobby: 0,
+            cars: vec![car(0, -2, 1), car(1, 0, 2)],
+            waiting: vec![(-1, 3)],
+            selected: 0,
+            origin: None,
+            scroll: 0,
+            compact: false,
+        };
+        let area = Rect::new(0, 0, 17, 4);
+        let mut buf = Buffer::empty(area);
+        view().render(area, &mut buf);
+        let rows: Vec<String> = (0..4)
+            .map(|y| {
+                (0..17)
+                    .map(|x| buf.get(x, y).symbol().to_string())
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                " 1      │   │[↑]│",
+                " 0      │   │[↑]│",
+                "-1 ☺3   │   │   │",
+                "-2      │[↑]│   │",
+            ]
+        );
+        assert_eq!(view().hit(area, 4, 2), Some(Hit::Landing(-1)));
+        assert_eq!(view().hit(area, 9, 0), Some(Hit::Lift(0)));
+        assert_eq!(view().hit(area, 15, 3), Some(Hit::Lift(1)));
+        assert_eq!(view().hit(area, 16, 3), None);
+    }
+
+    #[test]
+    fn scrolls_to_the_selected_car() {
+        let car = |id, floor| Car {
+            id,
+            floor,
+            decks: 1,
+            doors_open: false,
+            fault: None,
+            symbol: '↑',
+        };
+        let view = ShaftView {
+            lowest: 0,
+            highest: 99,
+            lobby: 0,
+            cars: (0..40).map(|id| car(id, id as i32 * 2)).collect(),
+            waiting: vec![],
+            selected: 25,
+            origin: None,
+            scroll: 0,
+            compact: true,
+        };
+        // Room for 20 floors and 10 compact shafts.
+        let area = Rect::new(0, 0, 29, 20);
+        assert_eq!(
+            view.scrolled(area),
+            Some("floors 40 to 59, lifts 20 to 29 of 40".to_string())
+        );
+        assert_eq!(view.hit(area, 18, 9), Some(Hit::Lift(25)));
+        assert_eq!(view.hit(area, 0, 19), Some(Hit::Landing(40)));
+        let view = view.scroll(-50);
+        assert_eq!(view.hit(area, 0, 19), Some(Hit::Landing(0)));
+        assert_eq!(top_floor(0, 10, 20, 3, 5), 10);
+    }
+}
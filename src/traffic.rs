@@ -0,0 +1,174 @@
+use crate::clock::Instant;
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Duration,
+};
+
+/// How long calls are remembered for.
+const WINDOW: Duration = Duration::from_secs(300);
+
+/// The fewest calls in the window needed to recognise a pattern.
+const MIN_CALLS: usize = 10;
+
+/// The share of calls of one kind needed for it to dominate the traffic.
+const DOMINANT_SHARE: f64 = 0.6;
+
+/// The kind of traffic the building is seeing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pattern {
+    /// Too few calls to tell.
+    Quiet,
+    /// Mostly people arriving at a lobby and going up into the building.
+    Incoming,
+    /// Mostly people leaving the building for a lobby.
+    Outgoing,
+    /// Mostly people moving between upper floors.
+    Interfloor,
+    /// No one kind of journey dominates.
+    Mixed,
+}
+
+impl std::fmt::Display for Pattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pattern::Quiet => write!(f, "quiet"),
+            Pattern::Incoming => write!(f, "incoming"),
+            Pattern::Outgoing => write!(f, "outgoing"),
+            Pattern::Interfloor => write!(f, "interfloor"),
+            Pattern::Mixed => write!(f, "mixed"),
+        }
+    }
+}
+
+/// A summary of the calls in the window.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Traffic {
+    pub incoming: usize,
+    pub outgoing: usize,
+    pub interfloor: usize,
+    /// Calls per minute.
+    pub rate: f64,
+    pub pattern: Pattern,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Call {
+    at: Instant,
+    from_floor: i32,
+    to_floor: i32,
+}
+
+/// A sliding window of recent calls for lifts.
+#[derive(Debug)]
+pub struct CallLog {
+    started: Instant,
+    calls: VecDeque<Call>,
+}
+
+impl Default for CallLog {
+    fn default() -> Self {
+        CallLog {
+            started: Instant::now(),
+            calls: VecDeque::new(),
+        }
+    }
+}
+
+impl CallLog {
+    pub fn record(&mut self, from_floor: i32, to_floor: i32) {
+        while self.calls.front().is_some_and(|c| c.at.elapsed() > WINDOW) {
+            self.calls.pop_front();
+        }
+        self.calls.push_back(Call {
+            at: Instant::now(),
+            from_floor,
+            to_floor,
+        });
+    }
+
+    fn recent(&self) -> impl Iterator<Item = &Call> {
+        self.calls.iter().filter(|c| c.at.elapsed() <= WINDOW)
+    }
+
+    /// Floors that recent calls have come from, busiest first.
+    pub fn demand(&self) -> Vec<i32> {
+        let mut counts: HashMap<i32, usize> = HashMap::new();
+        for call in self.recent() {
+            *counts.entry(call.from_floor).or_default() += 1;
+        }
+        let mut floors: Vec<(i32, usize)> = counts.into_iter().collect();
+        floors.sort_by_key(|(floor, count)| (usize::MAX - count, *floor));
+        floors.into_iter().map(|(floor, _)| floor).collect()
+    }
+
+    /// Classify recent calls as coming from a lobby, going to a lobby, or
+    /// between other floors, and work out which kind dominates.
+    pub fn traffic(&self, is_lobby: impl Fn(i32) -> bool) -> Traffic {
+        let (mut incoming, mut outgoing, mut interfloor) = (0, 0, 0);
+        for call in self.recent() {
+            if is_lobby(call.from_floor) {
+                incoming += 1;
+            } else if is_lobby(call.to_floor) {
+                outgoing += 1;
+            } else {
+                interfloor += 1;
+            }
+        }
+        let total = incoming + outgoing + interfloor;
+        let dominates = |count: usize| count as f64 >= DOMINANT_SHARE * total as f64;
+        let pattern = if total < MIN_CALLS {
+            Pattern::Quiet
+        } else if dominates(incoming) {
+            Pattern::Incoming
+        } else if dominates(outgoing) {
+            Pattern::Outgoing
+        } else if dominates(interfloor) {
+            Pattern::Interfloor
+        } else {
+            Pattern::Mixed
+        };
+        let minutes = self.started.elapsed().min(WINDOW).as_secs_f64() / 60.0;
+        Traffic {
+            incoming,
+            outgoing,
+            interfloor,
+            rate: if minutes > 0.0 {
+                total as f64 / minutes
+            } else {
+                0.0
+            },
+            pattern,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::traffic::*;
+
+    #[test]
+    fn classify_traffic() {
+        let is_lobby = |floor| floor == 0;
+        let mut log = CallLog::default();
+        for floor in 1..=5 {
+            log.record(0, floor);
+        }
+        assert_eq!(log.traffic(is_lobby).pattern, Pattern::Quiet);
+        for floor in 1..=5 {
+            log.record(0, floor);
+        }
+        log.record(3, 0);
+        let traffic = log.traffic(is_lobby);
+        assert_eq!((traffic.incoming, traffic.outgoing), (10, 1));
+        assert_eq!(traffic.pattern, Pattern::Incoming);
+        for floor in 1..=10 {
+            log.record(floor, 0);
+        }
+        assert_eq!(log.traffic(is_lobby).pattern, Pattern::Mixed);
+        for floor in 1..=40 {
+            log.record(floor, floor + 1);
+        }
+        assert_eq!(log.traffic(is_lobby).pattern, Pattern::Interfloor);
+        assert_eq!(log.demand()[..2], [0, 3]);
+    }
+}